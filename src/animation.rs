@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+
+use crate::{
+    enemy::{Archetype, Archetypes, EnemyActivity, EnemyRes, PathfindPath},
+    nodes::Health,
+};
+
+// every frame of the StormHead sheets, stacked from top to bottom
+const FRAME: UVec2 = UVec2::new(119, 124);
+
+// the sprite is brightened while the damaged clip plays
+const FLASH: Color = Color::srgb(10., 10., 10.);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clip {
+    Idle,
+    Run,
+    Attack,
+    Damaged,
+    Death,
+}
+
+impl Clip {
+    fn frame_secs(&self) -> f32 {
+        match self {
+            // one swing per attack, attacks happen every second
+            Clip::Attack => 1. / 21.,
+            Clip::Run => 0.08,
+            Clip::Idle | Clip::Damaged | Clip::Death => 0.1,
+        }
+    }
+
+    // the others play once and stop on their last frame
+    fn looping(&self) -> bool {
+        matches!(self, Clip::Idle | Clip::Run | Clip::Attack)
+    }
+}
+
+pub struct Sheet {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    pub frames: usize,
+}
+
+impl Sheet {
+    fn load(
+        ass: &AssetServer,
+        layouts: &mut Assets<TextureAtlasLayout>,
+        path: &str,
+        frames: usize,
+    ) -> Self {
+        Self {
+            image: ass.load(path),
+            layout: layouts.add(TextureAtlasLayout::from_grid(
+                FRAME,
+                1,
+                frames as u32,
+                None,
+                None,
+            )),
+            frames,
+        }
+    }
+}
+
+// the StormHead sprite sheets every enemy is drawn with
+pub struct Sheets {
+    idle: Sheet,
+    run: Sheet,
+    attack: Sheet,
+    damaged: Sheet,
+    death: Sheet,
+}
+
+impl Sheets {
+    pub fn load(ass: &AssetServer, layouts: &mut Assets<TextureAtlasLayout>) -> Self {
+        Self {
+            idle: Sheet::load(ass, layouts, "stormhead/idle.png", 9),
+            run: Sheet::load(ass, layouts, "stormhead/run.png", 10),
+            attack: Sheet::load(ass, layouts, "stormhead/attack.png", 21),
+            damaged: Sheet::load(ass, layouts, "stormhead/damaged.png", 2),
+            death: Sheet::load(ass, layouts, "stormhead/death.png", 9),
+        }
+    }
+
+    pub fn get(&self, clip: Clip) -> &Sheet {
+        match clip {
+            Clip::Idle => &self.idle,
+            Clip::Run => &self.run,
+            Clip::Attack => &self.attack,
+            Clip::Damaged => &self.damaged,
+            Clip::Death => &self.death,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Animation {
+    pub clip: Clip,
+    frame: usize,
+    timer: Timer,
+    // set once a clip that doesn't loop reached its last frame
+    done: bool,
+}
+
+impl Animation {
+    pub fn new(clip: Clip) -> Self {
+        Self {
+            clip,
+            frame: 0,
+            timer: Timer::from_seconds(clip.frame_secs(), TimerMode::Repeating),
+            done: false,
+        }
+    }
+
+    pub fn play(&mut self, clip: Clip) {
+        *self = Self::new(clip);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.done
+    }
+}
+
+// the health an enemy had last frame, to notice it got hit
+#[derive(Component)]
+pub struct SeenHealth(pub f32);
+
+// run while walking, attack while attacking, idle once the path is walked
+pub fn pick_clip(mut ents: Query<(&EnemyActivity, &PathfindPath, &mut Animation)>) {
+    for (activity, path, mut anim) in ents.iter_mut() {
+        let clip = match activity {
+            EnemyActivity::Attacking(..) => Clip::Attack,
+            EnemyActivity::Pathfinding if path.next().is_none() => Clip::Idle,
+            EnemyActivity::Pathfinding => Clip::Run,
+        };
+
+        // getting hit finishes playing first, dying is never interrupted
+        let busy = match anim.clip {
+            Clip::Death => true,
+            Clip::Damaged => !anim.finished(),
+            _ => false,
+        };
+        if anim.clip != clip && !busy {
+            anim.play(clip);
+        }
+    }
+}
+
+// plays the damaged clip and flashes the sprite when an enemy loses health
+pub fn flash_damage(
+    archetypes: Res<Archetypes>,
+    mut ents: Query<(
+        &Health,
+        &mut SeenHealth,
+        &mut Animation,
+        &mut Sprite,
+        &Archetype,
+    )>,
+) {
+    for (hp, mut seen, mut anim, mut sprite, archetype) in ents.iter_mut() {
+        let hit = hp.0 < seen.0;
+        seen.0 = hp.0;
+        if hit && hp.0 > 0. && anim.clip != Clip::Damaged && anim.clip != Clip::Death {
+            anim.play(Clip::Damaged);
+        }
+
+        let flashing = anim.clip == Clip::Damaged && !anim.finished();
+        let color = if flashing {
+            FLASH
+        } else {
+            archetypes.get(archetype).color()
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+pub fn animate(
+    time: Res<Time>,
+    res: Res<EnemyRes>,
+    mut ents: Query<(&mut Animation, &mut TextureAtlas, &mut Handle<Image>)>,
+) {
+    for (mut anim, mut atlas, mut image) in ents.iter_mut() {
+        let sheet = res.sheets.get(anim.clip);
+        if *image != sheet.image {
+            *image = sheet.image.clone();
+            atlas.layout = sheet.layout.clone();
+        }
+
+        let steps = anim.timer.tick(time.delta()).times_finished_this_tick() as usize;
+        if !anim.done {
+            let frame = anim.frame + steps;
+            if frame < sheet.frames {
+                anim.frame = frame;
+            } else if anim.clip.looping() {
+                anim.frame = frame % sheet.frames;
+            } else {
+                anim.frame = sheet.frames - 1;
+                anim.done = true;
+            }
+        }
+
+        if atlas.index != anim.frame {
+            atlas.index = anim.frame;
+        }
+    }
+}
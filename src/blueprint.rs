@@ -0,0 +1,335 @@
+use std::{fs, path::Path};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use hexx::Hex;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    adjacent,
+    catalog::NodeItems,
+    economy::{Credits, Price},
+    nodes::{CyberState, NodeBundle, NodeKind, PortCfg, PortMeta, PortMetas, NODE_HP},
+    save::{hex, saved_hex, SavedHex, SavedVal},
+    Map, Selection, TileType, HEX_SIZE,
+};
+
+const EXPORT_PATH: &str = "blueprints/clipboard.ron";
+// copying more than this is better done with several blueprints
+const MAX_RADIUS: u32 = 4;
+
+// a group of wired nodes, relative to the tile it was copied around
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Blueprint {
+    pub nodes: Vec<BlueprintNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlueprintNode {
+    pub offset: SavedHex,
+    pub node: NodeKind,
+    // offset to the input node and the name of the port it is wired to
+    pub inputs: Vec<(SavedHex, String)>,
+    pub constant: Option<SavedVal>,
+}
+
+#[derive(Debug, Error)]
+pub enum BlueprintError {
+    #[error("could not access blueprint file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write blueprint: {0}")]
+    Ser(#[from] ron::Error),
+    #[error("could not parse blueprint: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+// the blueprint picked up in the game, pasted with the current rotation
+#[derive(Resource)]
+pub struct Clipboard {
+    pub blueprint: Option<Blueprint>,
+    // tiles around the mouse that are copied
+    pub radius: u32,
+    // clockwise steps of 60 degrees
+    pub rotation: u32,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            blueprint: None,
+            radius: 1,
+            rotation: 0,
+        }
+    }
+}
+
+impl Blueprint {
+    pub fn to_text(&self) -> Result<String, BlueprintError> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?)
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, BlueprintError> {
+        Ok(ron::de::from_str(text)?)
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), BlueprintError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text()?)?;
+        Ok(())
+    }
+
+    pub fn import(path: &Path) -> Result<Self, BlueprintError> {
+        Self::from_text(&fs::read_to_string(path)?)
+    }
+
+    // port inputs are relative offsets, so they turn together with the nodes
+    pub fn rotated(&self, rotation: u32) -> Self {
+        let rotate = |h: SavedHex| saved_hex(hex(h).rotate_cw(rotation % 6));
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|n| BlueprintNode {
+                    offset: rotate(n.offset),
+                    node: n.node.clone(),
+                    inputs: n
+                        .inputs
+                        .iter()
+                        .map(|(offset, port)| (rotate(*offset), port.clone()))
+                        .collect(),
+                    constant: n.constant.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// every node within `radius` of `center`, the heart and terrain are left out
+pub fn capture(world: &World, center: Hex, radius: u32) -> Blueprint {
+    let map = world.resource::<Map>();
+    let nodes = center
+        .range(radius)
+        .filter_map(|tile| {
+            let te = map.storage.get(tile)?;
+            let TileType::CyberNode { meta, e } = *world.get::<TileType>(*te)? else {
+                return None;
+            };
+
+            let cfg = world.get::<PortCfg>(e)?;
+            let mut inputs: Vec<_> = cfg
+                .inputs
+                .iter()
+                .filter_map(|(offset, pe)| {
+                    Some((saved_hex(*offset), world.get::<PortMeta>(*pe)?.name.clone()))
+                })
+                .collect();
+            inputs.sort();
+
+            Some(BlueprintNode {
+                offset: saved_hex(tile - center),
+                node: world.get::<NodeKind>(meta)?.clone(),
+                inputs,
+                constant: cfg
+                    .constant
+                    .as_ref()
+                    .map(|v| SavedVal::new(v, &HashMap::default())),
+            })
+        })
+        .collect();
+
+    Blueprint { nodes }
+}
+
+// places the blueprint around `center` the way the shop would: only on unoccupied tiles next to
+// the heart or a connected node, and as long as it's affordable. pasted nodes count as
+// connected, so the blueprint grows out from the heart. returns the number of placed nodes
+pub fn paste(world: &mut World, blueprint: &Blueprint, center: Hex) -> usize {
+    let by_node = world.resource::<NodeItems>().by_node.clone();
+    let mut pasted = HashSet::new();
+    let mut pending: Vec<&BlueprintNode> = blueprint.nodes.iter().collect();
+
+    loop {
+        let connected = |te| match world.get::<TileType>(te) {
+            Some(TileType::CyberNode { e, .. }) => {
+                pasted.contains(e) || world.get::<CyberState>(*e) != Some(&CyberState::Disabled)
+            }
+            Some(TileType::Heart(_)) => true,
+            _ => false,
+        };
+        let map = world.resource::<Map>();
+        let Some(i) = pending
+            .iter()
+            .position(|bp| adjacent(map, center + hex(bp.offset), connected))
+        else {
+            break;
+        };
+
+        let bp = pending.remove(i);
+        if let Some(e) = place(world, bp, center + hex(bp.offset), &by_node) {
+            pasted.insert(e);
+        }
+    }
+
+    for bp in pending {
+        let tile = center + hex(bp.offset);
+        warn!("blueprint: tile {:?} is not connected, skipping", tile);
+    }
+
+    pasted.len()
+}
+
+fn place(
+    world: &mut World,
+    bp: &BlueprintNode,
+    tile: Hex,
+    by_node: &HashMap<NodeKind, Entity>,
+) -> Option<Entity> {
+    let Some(te) = world.resource::<Map>().storage.get(tile).copied() else {
+        warn!("blueprint: tile {:?} is out of bounds, skipping", tile);
+        return None;
+    };
+    if *world.get::<TileType>(te).unwrap() != TileType::Unoccupied {
+        warn!("blueprint: tile {:?} is occupied, skipping", tile);
+        return None;
+    }
+    let Some(meta) = by_node.get(&bp.node).copied() else {
+        warn!(
+            "blueprint: {:?} is not in the node catalog, skipping",
+            bp.node
+        );
+        return None;
+    };
+    let price = world.get::<Price>(meta).copied().unwrap_or_default();
+    if !world.resource_mut::<Credits>().spend(price) {
+        warn!("blueprint: can't afford {:?}, skipping", bp.node);
+        return None;
+    }
+
+    let ports = world.get::<PortMetas>(meta).unwrap().0.clone();
+    let inputs = bp
+        .inputs
+        .iter()
+        .filter_map(|(offset, name)| {
+            let pe = ports
+                .iter()
+                .copied()
+                .find(|pe| world.get::<PortMeta>(*pe).unwrap().name == *name);
+            if pe.is_none() {
+                warn!(
+                    "blueprint: {:?} has no port {:?}, dropping input",
+                    bp.node, name
+                );
+            }
+            Some((hex(*offset), pe?))
+        })
+        .collect();
+
+    let cfg = PortCfg {
+        inputs,
+        constant: bp.constant.as_ref().map(|v| v.val(&[])),
+    };
+    let e = world
+        .spawn(NodeBundle::placed(meta, cfg, tile, NODE_HP))
+        .id();
+
+    let mat = world.get::<Handle<ColorMaterial>>(meta).unwrap().clone();
+    *world.get_mut::<TileType>(te).unwrap() = TileType::CyberNode { meta, e };
+    *world.get_mut::<Handle<ColorMaterial>>(te).unwrap() = mat;
+    Some(e)
+}
+
+// holding C shows the tiles around the hovered one that get copied once it's released,
+// [ and ] shrink and grow them. V pastes at the hovered tile, R rotates the clipboard.
+// E exports the clipboard to a file and I imports it again
+pub fn blueprint_keys(
+    mut cmd: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut clipboard: ResMut<Clipboard>,
+) {
+    if keys.just_pressed(KeyCode::BracketLeft) && clipboard.radius > 0 {
+        clipboard.radius -= 1;
+        info!("blueprint copy radius: {}", clipboard.radius);
+    }
+
+    if keys.just_pressed(KeyCode::BracketRight) && clipboard.radius < MAX_RADIUS {
+        clipboard.radius += 1;
+        info!("blueprint copy radius: {}", clipboard.radius);
+    }
+
+    if keys.just_pressed(KeyCode::KeyR) {
+        clipboard.rotation = (clipboard.rotation + 1) % 6;
+        info!("blueprint rotation: {}", clipboard.rotation);
+    }
+
+    if keys.just_pressed(KeyCode::KeyE) {
+        let Some(bp) = &clipboard.blueprint else {
+            warn!("blueprint: nothing to export");
+            return;
+        };
+        match bp.export(Path::new(EXPORT_PATH)) {
+            Ok(()) => info!("exported blueprint to {:?}", EXPORT_PATH),
+            Err(e) => error!("could not export blueprint: {}", e),
+        }
+    }
+
+    if keys.just_pressed(KeyCode::KeyI) {
+        match Blueprint::import(Path::new(EXPORT_PATH)) {
+            Ok(bp) => {
+                info!("imported blueprint with {} nodes", bp.nodes.len());
+                clipboard.blueprint = Some(bp);
+                clipboard.rotation = 0;
+            }
+            Err(e) => error!("could not import blueprint: {}", e),
+        }
+    }
+
+    let Some(tile) = selection.mouseover else {
+        return;
+    };
+
+    if keys.just_released(KeyCode::KeyC) {
+        let radius = clipboard.radius;
+        cmd.add(move |world: &mut World| {
+            let bp = capture(world, tile, radius);
+            info!("copied blueprint with {} nodes", bp.nodes.len());
+            let mut clipboard = world.resource_mut::<Clipboard>();
+            clipboard.blueprint = Some(bp);
+            clipboard.rotation = 0;
+        });
+    }
+
+    if keys.just_pressed(KeyCode::KeyV) {
+        let Some(bp) = &clipboard.blueprint else {
+            return;
+        };
+        let bp = bp.rotated(clipboard.rotation);
+        cmd.add(move |world: &mut World| {
+            let placed = paste(world, &bp, tile);
+            info!("pasted {} of {} blueprint nodes", placed, bp.nodes.len());
+        });
+    }
+}
+
+// outlines the tiles that are copied while C is held
+pub fn draw_copy_region(
+    mut gizmos: Gizmos,
+    keys: Res<ButtonInput<KeyCode>>,
+    map: Res<Map>,
+    selection: Res<Selection>,
+    clipboard: Res<Clipboard>,
+) {
+    let Some(tile) = selection.mouseover.filter(|_| keys.pressed(KeyCode::KeyC)) else {
+        return;
+    };
+    for h in tile.range(clipboard.radius) {
+        let center = map.layout.hex_to_world_pos(h);
+        gizmos.circle_2d(center, HEX_SIZE.x * 0.8, Color::WHITE);
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::{
+    ecs::system::{
+        lifetimeless::{SCommands, SQuery, SRes, SResMut},
+        SystemParamItem,
+    },
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::Deserialize;
+
+use crate::economy::Price;
+use crate::nodes::{
+    Description, IconLabel, ItemMeta, ItemMetaBundle, Name, NodeKind, OutputPort, PortCfg,
+    PortMeta, PortMetas, ValType,
+};
+use crate::ron_asset::{RegisterRonAsset, RonAsset};
+
+pub const CATALOG_PATH: &str = "nodes.catalog.ron";
+
+pub struct CatalogPlugin;
+impl Plugin for CatalogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NodeItems>()
+            .add_event::<CatalogChanged>()
+            .register_ron_asset::<NodeCatalog>();
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PortDef {
+    pub name: String,
+    pub desc: String,
+    pub vt: ValType,
+    #[serde(default)]
+    pub constant: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NodeDef {
+    pub node: NodeKind,
+    pub name: String,
+    pub desc: String,
+    pub texture: String,
+    // drawn over the texture, so nodes sharing one can be told apart
+    #[serde(default)]
+    pub label: Option<String>,
+    // free if not set
+    #[serde(default)]
+    pub price: u32,
+    #[serde(default)]
+    pub inputs: Vec<PortDef>,
+    #[serde(default = "PortDef::nothing")]
+    pub output: PortDef,
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct NodeCatalog {
+    pub nodes: Vec<NodeDef>,
+}
+
+// sent whenever the item metas were (re)built from the catalog
+#[derive(Event)]
+pub struct CatalogChanged;
+
+// the item meta entities spawned from the catalog
+#[derive(Resource, Default)]
+pub struct NodeItems {
+    // in catalog order, used for the shop layout
+    pub order: Vec<Entity>,
+    pub by_node: HashMap<NodeKind, Entity>,
+}
+
+impl PortDef {
+    fn nothing() -> Self {
+        Self {
+            name: "nothing".to_string(),
+            desc: "nothing".to_string(),
+            vt: ValType::Empty,
+            constant: false,
+        }
+    }
+
+    fn meta(&self) -> PortMeta {
+        PortMeta::new_meta(self.name.clone(), self.desc.clone(), self.vt, self.constant)
+    }
+}
+
+impl RonAsset for NodeCatalog {
+    const PATH: &'static str = CATALOG_PATH;
+    const EXTENSIONS: &'static [&'static str] = &["catalog.ron"];
+
+    type Param = (
+        SCommands,
+        EventWriter<'static, CatalogChanged>,
+        SRes<AssetServer>,
+        SResMut<Assets<ColorMaterial>>,
+        SResMut<NodeItems>,
+        SQuery<
+            (
+                &'static PortMetas,
+                &'static OutputPort,
+                &'static Handle<ColorMaterial>,
+            ),
+            With<ItemMeta>,
+        >,
+        SQuery<&'static mut PortCfg>,
+    );
+
+    fn apply(
+        &self,
+        (cmd, changed, ass, mats, items, existing, cfgs): &mut SystemParamItem<Self::Param>,
+    ) {
+        info!("applying node catalog with {} nodes", self.nodes.len());
+        items.order.clear();
+        let mut seen = HashSet::new();
+        for def in self.nodes.iter() {
+            if !seen.insert(&def.node) {
+                warn!("node {:?} is in the catalog twice, skipping", def.node.0);
+                continue;
+            }
+
+            let tex: Handle<Image> = ass.load(&def.texture);
+
+            let Some(item) = items.by_node.get(&def.node).copied() else {
+                let inputs: Vec<Entity> = def
+                    .inputs
+                    .iter()
+                    .map(|p| cmd.spawn(p.meta()).id())
+                    .collect();
+                let output = cmd.spawn(def.output.meta()).id();
+                let item = cmd
+                    .spawn(ItemMetaBundle::new(
+                        def.name.clone(),
+                        def.desc.clone(),
+                        &inputs,
+                        output,
+                        tex,
+                        def.node.clone(),
+                        Price(def.price),
+                        mats,
+                    ))
+                    .id();
+                if let Some(label) = &def.label {
+                    cmd.entity(item).insert(IconLabel(label.clone()));
+                }
+
+                items.by_node.insert(def.node.clone(), item);
+                items.order.push(item);
+                continue;
+            };
+
+            // the item is already placed on the map, update it in place
+            // so MetaLinks and wired ports stay valid
+            let Ok((old_inputs, output, mat)) = existing.get(item) else {
+                warn!("item for {:?} is gone, skipping", def.node.0);
+                continue;
+            };
+            let mut inputs = Vec::new();
+            for (i, p) in def.inputs.iter().enumerate() {
+                let pe = match old_inputs.0.get(i) {
+                    Some(pe) => {
+                        cmd.entity(*pe).insert(p.meta());
+                        *pe
+                    }
+                    None => cmd.spawn(p.meta()).id(),
+                };
+                inputs.push(pe);
+            }
+
+            for removed in old_inputs.0.iter().skip(def.inputs.len()) {
+                for mut cfg in cfgs.iter_mut() {
+                    cfg.inputs.retain(|_, pe| pe != removed);
+                }
+                cmd.entity(*removed).despawn();
+            }
+
+            cmd.entity(output.0).insert(def.output.meta());
+            if let Some(mat) = mats.get_mut(mat) {
+                mat.texture = Some(tex.clone());
+            }
+            cmd.entity(item).insert((
+                Name(def.name.clone()),
+                Description(def.desc.clone()),
+                tex,
+                PortMetas(inputs),
+                Price(def.price),
+            ));
+            match &def.label {
+                Some(label) => cmd.entity(item).insert(IconLabel(label.clone())),
+                None => cmd.entity(item).remove::<IconLabel>(),
+            };
+
+            items.order.push(item);
+        }
+
+        changed.send(CatalogChanged);
+    }
+}
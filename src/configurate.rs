@@ -1,7 +1,7 @@
 use core::panic;
 
 use bevy::{
-    color::palettes::css::BLACK,
+    color::palettes::css::{BLACK, RED},
     input::{
         keyboard::{Key, KeyboardInput},
         ButtonState,
@@ -13,7 +13,10 @@ use bevy::{
 use hexx::{storage::HexagonalMap, Hex, HexLayout};
 
 use crate::{
-    nodes::{MetaLink, OutputPort, PortCfg, PortMeta, PortMetas, Val as CyberVal, ValType},
+    nodes::{
+        CyberState, HexPos, MetaLink, OutputPort, OutputTypes, PortCfg, PortMeta, PortMetas,
+        Val as CyberVal, ValType, WiringError, WiringErrors,
+    },
     ui::UIRoot,
     CommonResources, ConfiguringTile, Gamestate, Map, TileType,
 };
@@ -52,12 +55,24 @@ struct GridSelection {
 #[derive(Component)]
 struct ConstantValue(Entity);
 
+// tint of a grid tile, depending on whether its node fits the port
+fn grid_tint(types: &OutputTypes, port: &PortMeta, at: Hex) -> Color {
+    match types.at(at) {
+        None => Color::WHITE,
+        Some(vt) if port.vt.accepts(vt) => Color::srgb(0.2, 1.1, 0.5),
+        Some(_) => Color::from(RED),
+    }
+}
+
 fn grid_pick(
     input: Res<ButtonInput<MouseButton>>,
     grids: Query<(&CfgGrid, &GridSelection, &GridTiles, &GridMeta)>,
     mut portcfg: Query<&mut PortCfg>,
     common: Res<CommonResources>,
     mut img: Query<&mut UiImage>,
+    types: OutputTypes,
+    positions: Query<&HexPos>,
+    metas: Query<&PortMeta>,
 ) {
     if !input.just_pressed(MouseButton::Left) {
         return;
@@ -72,6 +87,16 @@ fn grid_pick(
             continue;
         };
 
+        let pos = positions.get(grid.0).expect("node without position").0;
+        let port = metas.get(meta.0).unwrap();
+        // an empty tile may still get a node, check_wiring flags it until then
+        if tile_hex != Hex::ZERO {
+            if let Err(e @ WiringError::Mismatch { .. }) = types.check(port, pos + tile_hex) {
+                warn!("can not wire port: {}", e);
+                continue;
+            }
+        }
+
         let mut cfg = portcfg.get_mut(grid.0).expect("node without portcfg");
         if let Some(h) = cfg
             .inputs
@@ -188,6 +213,7 @@ fn spawn_output_row(
             let v = match &cfg.constant {
                 Some(crate::nodes::Val::Text(t)) => t.clone(),
                 Some(crate::nodes::Val::Number(n)) => n.to_string(),
+                Some(crate::nodes::Val::Bool(b)) => b.to_string(),
                 Some(_) => unreachable!(),
                 None => "use the keyboard :)".to_string(),
             };
@@ -209,7 +235,11 @@ fn spawn_input_row(
     res: &CommonResources,
     node: Entity,
     layout: &HexLayout,
+    types: &OutputTypes,
+    pos: Hex,
+    errors: Option<&WiringErrors>,
 ) -> Entity {
+    let meta = metas.get(meta_ent).unwrap();
     let current = cfg
         .inputs
         .iter()
@@ -224,7 +254,12 @@ fn spawn_input_row(
             img = res.port_in.clone();
         }
 
-        let pos = layout.hex_to_world_pos(h);
+        let mut image = UiImage::new(img);
+        if h != Hex::ZERO {
+            image.color = grid_tint(types, meta, pos + h);
+        }
+
+        let ui_pos = layout.hex_to_world_pos(h);
         let ts = layout.rect_size();
         let hts = ts / 2.;
         cmd.spawn(ImageBundle {
@@ -232,19 +267,19 @@ fn spawn_input_row(
                 position_type: PositionType::Absolute,
                 width: Val::Px(ts.x),
                 height: Val::Px(ts.y),
-                top: Val::Px(-pos.y + half_size.y - hts.y),
-                left: Val::Px(pos.x + half_size.x - hts.x),
+                top: Val::Px(-ui_pos.y + half_size.y - hts.y),
+                left: Val::Px(ui_pos.x + half_size.x - hts.x),
                 margin: UiRect::all(Val::Px(2.)),
                 ..default()
             },
-            image: UiImage::new(img),
+            image,
             ..default()
         })
         .id()
     });
 
     let tile_ents: Vec<Entity> = tiles.iter().flatten().copied().collect();
-    let meta = metas.get(meta_ent).unwrap();
+    let error = errors.and_then(|errors| errors.0.iter().find(|e| e.port() == meta.name));
 
     cmd.spawn(NodeBundle {
         style: Style {
@@ -277,6 +312,15 @@ fn spawn_input_row(
                 meta.vt.to_string(),
                 TextStyle::default(),
             ));
+            if let Some(error) = error {
+                ui.spawn(TextBundle::from_section(
+                    error.to_string(),
+                    TextStyle {
+                        color: RED.into(),
+                        ..default()
+                    },
+                ));
+            }
         });
 
         // right side (grid)
@@ -348,6 +392,11 @@ fn update_constant(
                 CyberVal::Number(v)
             }
             ValType::Text => CyberVal::Text(text.sections[0].value.clone()),
+            // anything but true or 1 is false
+            ValType::Bool => {
+                let v = text.sections[0].value.trim().to_lowercase();
+                CyberVal::Bool(v == "true" || v == "1")
+            }
             _ => unreachable!(),
         };
 
@@ -365,6 +414,8 @@ fn build_ui(
     metas: Query<&PortMeta>,
     cfg: Query<&PortCfg>,
     res: Res<CommonResources>,
+    types: OutputTypes,
+    nodes: Query<(&HexPos, &CyberState, Option<&WiringErrors>)>,
 ) {
     let tt = tts.get(map.fetch_panic(tile.0.unwrap())).unwrap();
     let TileType::CyberNode { meta, e, .. } = *tt else {
@@ -372,6 +423,7 @@ fn build_ui(
     };
     let node = e;
     let cfg = cfg.get(e).unwrap();
+    let (pos, state, errors) = nodes.get(e).unwrap();
 
     let layout = HexLayout {
         hex_size: Vec2::splat(20.),
@@ -380,6 +432,20 @@ fn build_ui(
 
     let (inputs, output) = ports.get(meta).expect("cybernode with no meta");
 
+    let last_error = match state {
+        CyberState::Done(Err(e)) => format!("last error: {}", e),
+        _ => String::new(),
+    };
+    let error_text = cmd
+        .spawn(TextBundle::from_section(
+            last_error,
+            TextStyle {
+                color: RED.into(),
+                ..default()
+            },
+        ))
+        .id();
+
     let in_text = cmd
         .spawn(TextBundle::from_section(
             "input ports".to_string(),
@@ -389,7 +455,11 @@ fn build_ui(
     let in_rows: Vec<Entity> = inputs
         .0
         .iter()
-        .map(|e| spawn_input_row(&mut cmd, &metas, &cfg, *e, &res, node, &layout))
+        .map(|e| {
+            spawn_input_row(
+                &mut cmd, &metas, cfg, *e, &res, node, &layout, &types, pos.0, errors,
+            )
+        })
         .collect();
     let out_text = cmd
         .spawn(TextBundle::from_section(
@@ -415,6 +485,7 @@ fn build_ui(
             },
             CfgUi(layout),
         ))
+        .add_child(error_text)
         .add_child(in_text)
         .push_children(&in_rows)
         .add_child(out_text)
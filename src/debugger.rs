@@ -0,0 +1,277 @@
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+    utils::HashMap,
+};
+use hexx::Hex;
+
+use crate::{
+    nodes::{self, CyberState, HexPos},
+    scheduler::Evaluation,
+    ui::UIRoot,
+    Heartbeat, Map, Selection, Tick, TileType,
+};
+
+// pauses the simulation and steps through heartbeats node by node
+#[derive(Resource, Default)]
+pub struct Debugger {
+    pub paused: bool,
+    // lets the paused simulation run for one step
+    pub step: Option<Step>,
+    pub breakpoints: HashMap<Hex, Breakpoint>,
+    // the tile of the breakpoint that paused the simulation
+    pub hit: Option<Hex>,
+    // the tile and text of a value breakpoint that is being typed
+    pub entry: Option<(Hex, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    // evaluates a single node
+    Node,
+    // finishes the current heartbeat, or runs the next one
+    Heartbeat,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    Error,
+    Value(nodes::Val),
+}
+
+impl Breakpoint {
+    // a typed value is a number, true or false, two numbers separated by a comma for a vector,
+    // and text otherwise
+    pub fn typed(text: &str) -> Self {
+        let text = text.trim();
+        if let Ok(n) = text.parse::<f32>() {
+            return Self::Value(nodes::Val::Number(n));
+        }
+        if let Ok(b) = text.parse::<bool>() {
+            return Self::Value(nodes::Val::Bool(b));
+        }
+        if let Some((x, y)) = text.split_once(',') {
+            if let (Ok(x), Ok(y)) = (x.trim().parse(), y.trim().parse()) {
+                return Self::Value(nodes::Val::Vec(Vec2::new(x, y)));
+            }
+        }
+        Self::Value(nodes::Val::Text(text.to_string()))
+    }
+
+    pub fn hit(&self, state: &CyberState) -> bool {
+        match (self, state) {
+            (Self::Error, CyberState::Done(Err(_))) => true,
+            (Self::Value(v), CyberState::Done(Ok(out))) => v == out,
+            _ => false,
+        }
+    }
+}
+
+impl Debugger {
+    // whether the simulation may advance this fixed update
+    pub fn running(&self) -> bool {
+        !self.paused || self.step.is_some()
+    }
+
+    // checks the breakpoint of a node that was just evaluated, pauses if it is hit
+    pub fn evaluated(&mut self, tile: Hex, state: &CyberState) {
+        if self.step == Some(Step::Node) {
+            self.step = None;
+        }
+
+        if self.breakpoints.get(&tile).is_some_and(|b| b.hit(state)) {
+            info!("breakpoint hit at {:?}", tile);
+            self.paused = true;
+            self.step = None;
+            self.hit = Some(tile);
+        }
+    }
+}
+
+// run condition of the simulation sets
+pub fn sim_running(debugger: Res<Debugger>) -> bool {
+    debugger.running()
+}
+
+// run condition of the other key bindings, they are text while a breakpoint value is typed
+pub fn not_typing(debugger: Res<Debugger>) -> bool {
+    debugger.entry.is_none()
+}
+
+// a step while paused starts the next heartbeat right away instead of waiting for the heart
+pub fn step_heartbeat(
+    debugger: Res<Debugger>,
+    evaluation: Res<Evaluation>,
+    hearts: Query<Entity, With<Heartbeat>>,
+    mut tick: EventWriter<Tick>,
+) {
+    if !debugger.paused || debugger.step.is_none() || evaluation.in_progress() {
+        return;
+    }
+    for e in hearts.iter() {
+        tick.send(Tick(e));
+    }
+}
+
+// whatever is left of a step is used up once the fixed update ran
+pub fn end_step(mut debugger: ResMut<Debugger>) {
+    if debugger.paused && debugger.step.is_some() {
+        debugger.step = None;
+    }
+}
+
+// F6 pauses and resumes, F7 steps one node, F8 one heartbeat.
+// B cycles the breakpoint of the hovered tile between none, on error and on its current value.
+// shift+B types the value to break on instead, enter sets it and escape cancels
+pub fn debugger_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut input: EventReader<KeyboardInput>,
+    selection: Res<Selection>,
+    map: Res<Map>,
+    tiles: Query<&TileType>,
+    states: Query<&CyberState>,
+    mut debugger: ResMut<Debugger>,
+) {
+    if debugger.entry.is_some() {
+        type_breakpoint(&mut debugger, &mut input);
+        return;
+    }
+    // the key that started typing must not end up in the text
+    input.clear();
+
+    if keys.just_pressed(KeyCode::F6) {
+        debugger.paused = !debugger.paused;
+        debugger.hit = None;
+        info!("debugger paused: {}", debugger.paused);
+    }
+    if keys.just_pressed(KeyCode::F7) {
+        debugger.paused = true;
+        debugger.step = Some(Step::Node);
+    }
+    if keys.just_pressed(KeyCode::F8) {
+        debugger.paused = true;
+        debugger.step = Some(Step::Heartbeat);
+    }
+
+    let Some(tile) = selection.mouseover else {
+        return;
+    };
+    if !keys.just_pressed(KeyCode::KeyB) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        debugger.entry = Some((tile, String::new()));
+        return;
+    }
+
+    let state = map.storage.get(tile).and_then(|te| match tiles.get(*te) {
+        Ok(TileType::CyberNode { e, .. }) => states.get(*e).ok(),
+        _ => None,
+    });
+    let next = match (debugger.breakpoints.get(&tile), state) {
+        (None, _) => Some(Breakpoint::Error),
+        (Some(Breakpoint::Error), Some(CyberState::Done(Ok(v)))) => {
+            Some(Breakpoint::Value(v.clone()))
+        }
+        _ => None,
+    };
+    info!("breakpoint at {:?}: {:?}", tile, next);
+    match next {
+        Some(b) => debugger.breakpoints.insert(tile, b),
+        None => debugger.breakpoints.remove(&tile),
+    };
+}
+
+fn type_breakpoint(debugger: &mut Debugger, input: &mut EventReader<KeyboardInput>) {
+    for evt in input.read() {
+        if evt.state != ButtonState::Pressed {
+            continue;
+        }
+        let Some((tile, text)) = debugger.entry.as_mut() else {
+            return;
+        };
+        match &evt.logical_key {
+            Key::Character(c) => text.push_str(c),
+            Key::Space => text.push(' '),
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Enter => {
+                let (tile, b) = (*tile, Breakpoint::typed(text));
+                info!("breakpoint at {:?}: {:?}", tile, b);
+                debugger.breakpoints.insert(tile, b);
+                debugger.entry = None;
+            }
+            Key::Escape => debugger.entry = None,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DebuggerPanel;
+
+pub fn spawn_debugger_panel(mut cmd: Commands, root: Res<UIRoot>) {
+    let panel = cmd
+        .spawn((
+            TextBundle::from_section("", TextStyle::default()).with_style(Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.),
+                top: Val::Px(10.),
+                max_width: Val::Px(400.),
+                ..default()
+            }),
+            DebuggerPanel,
+        ))
+        .id();
+    cmd.entity(root.0).add_child(panel);
+}
+
+// shows the debugger state and the full value of the hovered node
+pub fn update_debugger_panel(
+    debugger: Res<Debugger>,
+    selection: Res<Selection>,
+    map: Res<Map>,
+    tiles: Query<&TileType>,
+    nodes: Query<(&HexPos, &CyberState)>,
+    mut panel: Query<&mut Text, With<DebuggerPanel>>,
+) {
+    let mut text = String::new();
+    if debugger.paused {
+        text.push_str("paused. F6 resume, F7 step node, F8 step heartbeat\n");
+    }
+    if let Some(hit) = debugger.hit {
+        text.push_str(&format!("breakpoint hit at ({}, {})\n", hit.x, hit.y));
+    }
+    if let Some((tile, entry)) = &debugger.entry {
+        text.push_str(&format!(
+            "break at ({}, {}) on value: {}_\n",
+            tile.x, tile.y, entry
+        ));
+    }
+
+    let hovered = selection
+        .mouseover
+        .and_then(|h| map.storage.get(h))
+        .and_then(|te| match tiles.get(*te) {
+            Ok(TileType::CyberNode { e, .. }) => nodes.get(*e).ok(),
+            _ => None,
+        });
+    if let Some((pos, state)) = hovered {
+        if let Some(b) = debugger.breakpoints.get(&pos.0) {
+            text.push_str(&format!("breakpoint: {:?}\n", b));
+        }
+        if debugger.paused || !debugger.breakpoints.is_empty() {
+            text.push_str(&format!("{:#?}", state));
+        }
+    }
+
+    for mut panel in panel.iter_mut() {
+        if panel.sections[0].value != text {
+            panel.sections[0].value.clone_from(&text);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use crate::{enemy::EnemyKilled, Tick};
+
+pub const STARTING_CREDITS: u32 = 100;
+// earned for every enemy that dies
+pub const KILL_REWARD: u32 = 5;
+// earned for every heartbeat
+pub const HEARTBEAT_INCOME: u32 = 1;
+
+// what nodes are bought with
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Credits(pub u32);
+
+impl Default for Credits {
+    fn default() -> Self {
+        Self(STARTING_CREDITS)
+    }
+}
+
+impl Credits {
+    pub fn can_afford(&self, price: Price) -> bool {
+        self.0 >= price.0
+    }
+
+    // takes the price if there is enough left
+    pub fn spend(&mut self, price: Price) -> bool {
+        if !self.can_afford(price) {
+            return false;
+        }
+        self.0 -= price.0;
+        true
+    }
+}
+
+// what an item costs in the shop
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Price(pub u32);
+
+impl Price {
+    // removing a node pays back half of what it cost
+    pub fn refund(&self) -> u32 {
+        self.0 / 2
+    }
+}
+
+pub fn income(
+    mut credits: ResMut<Credits>,
+    mut killed: EventReader<EnemyKilled>,
+    mut ticks: EventReader<Tick>,
+) {
+    let earned =
+        killed.read().count() as u32 * KILL_REWARD + ticks.read().count() as u32 * HEARTBEAT_INCOME;
+    if earned > 0 {
+        credits.0 += earned;
+    }
+}
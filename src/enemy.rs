@@ -1,50 +1,113 @@
-use std::time::Duration;
+use std::{collections::HashMap, f32::consts::TAU, time::Duration};
 
 use bevy::{
     color::palettes::css::{BLUE, GREEN, RED},
     prelude::*,
 };
-use hexx::{algorithms, Hex};
-use rand::{seq::IteratorRandom, Rng};
+use hexx::{Hex, HexLayout};
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    nodes::{Health, HexPos, TargetableEntity},
-    CommonResources, Debug, Gamestate, Map, Tick, TileType,
+    animation::{animate, flash_damage, pick_clip, Animation, Clip, SeenHealth, Sheets},
+    nodes::{DamageKind, Health, HexPos, Shield, TargetableEntity},
+    pathing::{replan, FlowField, FlowFields},
+    waves::{WaveDirector, Waves},
+    CommonResources, Debug, Map, SimRng, SimSet, Tick, TileType,
 };
 
 pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_res).add_systems(
-            FixedUpdate,
-            (
-                spawner,
-                attack,
-                activity_transition,
-                follow_path,
-                scale_enemy,
-                draw_path,
-                despawn,
-            )
-                .run_if(in_state(Gamestate::Game)),
-        );
+        app.add_event::<EnemyKilled>()
+            .init_resource::<Archetypes>()
+            .add_systems(Startup, load_res)
+            .add_systems(
+                FixedUpdate,
+                (
+                    replan,
+                    spawner,
+                    add_traits,
+                    attack,
+                    fly_bolts,
+                    activity_transition,
+                    follow_path,
+                    scale_enemy,
+                    pick_clip,
+                    flash_damage,
+                    animate,
+                    draw_path,
+                    die,
+                    despawn,
+                )
+                    .chain()
+                    .in_set(SimSet::Enemies),
+            );
     }
 }
 
+#[derive(Event)]
+pub struct EnemyKilled;
+
+// the heart the enemy walks to
 #[derive(Component)]
-struct PathfindTarget(Entity);
+pub struct PathfindTarget(pub Entity);
 
 #[derive(Component)]
 struct Dmg(f32);
 
+// killed, plays the death clip until it is despawned
+#[derive(Component)]
+pub struct Dying;
+
 #[derive(Component)]
 pub struct PathfindPath {
     pub path: Vec<Hex>,
     pub i: usize,
 }
 
+impl PathfindPath {
+    // the tile the enemy is walking to, `None` once it arrived
+    pub fn next(&self) -> Option<Hex> {
+        self.path.get(self.i).copied()
+    }
+
+    // the tile the enemy arrived at last, `None` before it arrived anywhere
+    pub fn last(&self) -> Option<Hex> {
+        self.path.get(self.i.checked_sub(1)?).copied()
+    }
+
+    // takes the way the field leads from the last tile,
+    // walks back to it first unless the next tile stays the same
+    pub fn follow(&mut self, field: &FlowField) {
+        let Some(path) = self.last().and_then(|h| field.path(h)) else {
+            return;
+        };
+        let i = if path.get(1).copied() == self.next() {
+            1
+        } else {
+            0
+        };
+        *self = Self { path, i };
+    }
+
+    // where an enemy at `pos` is after walking `dist` world units along the path
+    pub fn walk(&self, layout: &HexLayout, mut pos: Vec2, mut dist: f32) -> Vec2 {
+        for next in self.path.iter().skip(self.i) {
+            let to = layout.hex_to_world_pos(*next);
+            let step = pos.distance(to);
+            if step >= dist {
+                return pos + (to - pos).normalize_or_zero() * dist;
+            }
+            dist -= step;
+            pos = to;
+        }
+        pos
+    }
+}
+
 #[derive(Bundle)]
-struct EnemyBundle {
+pub struct EnemyBundle {
     apperance: SpriteBundle,
     target: PathfindTarget,
     path: PathfindPath,
@@ -52,54 +115,236 @@ struct EnemyBundle {
     targetable: TargetableEntity,
     health: Health,
     dmg: Dmg,
+    archetype: Archetype,
+    speed: Speed,
+    atlas: TextureAtlas,
+    animation: Animation,
+    seen_health: SeenHealth,
+}
+
+// what kind of enemy it is, picked per group of a wave.
+// the name circuits match enemies by, what it does is looked up in `Archetypes`
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Archetype(pub String);
+
+impl Default for Archetype {
+    fn default() -> Self {
+        Self::new("grunt")
+    }
+}
+
+impl Archetype {
+    pub fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+}
+
+// how an archetype differs from a plain enemy, set in the wave schedule
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ArchetypeDef {
+    // srgb, above 1 to glow
+    pub color: (f32, f32, f32),
+    // times `SPEED`
+    pub speed: f32,
+    // scales the hp rolled by the wave
+    pub hp: f32,
+    // scales the damage of every attack
+    pub dmg: f32,
+    // attacks from this many tiles away instead of walking into nodes
+    pub range: Option<u32>,
+    // splits into other enemies when it dies
+    pub split: Option<SplitDef>,
+    // damage kinds that don't hurt it
+    pub shield: Vec<DamageKind>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SplitDef {
+    pub children: u32,
+    pub into: Archetype,
+}
+
+// used for archetypes missing from the schedule
+static PLAIN: ArchetypeDef = ArchetypeDef {
+    color: (1., 1., 1.),
+    speed: 1.,
+    hp: 1.,
+    dmg: 1.,
+    range: None,
+    split: None,
+    shield: Vec::new(),
+};
+
+impl Default for ArchetypeDef {
+    fn default() -> Self {
+        PLAIN.clone()
+    }
+}
+
+impl ArchetypeDef {
+    pub fn color(&self) -> Color {
+        let (r, g, b) = self.color;
+        Color::srgb(r, g, b)
+    }
+}
+
+// every archetype of the wave schedule, by name
+#[derive(Resource, Default, Clone, Debug)]
+pub struct Archetypes(pub HashMap<Archetype, ArchetypeDef>);
+
+impl Archetypes {
+    pub fn get(&self, archetype: &Archetype) -> &ArchetypeDef {
+        self.0.get(archetype).unwrap_or(&PLAIN)
+    }
+
+    // sorted by name
+    pub fn names(&self) -> Vec<Archetype> {
+        let mut names: Vec<_> = self.0.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+// world units per second
+#[derive(Component, Clone, Copy)]
+pub struct Speed(pub f32);
+
+// attacks the closest node or heart within `range` tiles instead of walking into it
+#[derive(Component)]
+pub struct Ranged {
+    pub range: u32,
+}
+
+// spawns `children` enemies of the `into` archetype when it dies,
+// with `hp` each, scaled like any enemy of that archetype
+#[derive(Component)]
+pub struct Splitter {
+    pub children: u32,
+    pub into: Archetype,
+    pub hp: f32,
+}
+
+// shown flying from a ranged enemy to what it hit, the damage is already dealt
+#[derive(Component)]
+pub struct Bolt {
+    pub to: Vec2,
 }
 
 #[derive(Component, Default, PartialEq)]
-enum EnemyActivity {
+pub enum EnemyActivity {
     #[default]
     Pathfinding,
     Attacking(Entity, Timer),
 }
 
 #[derive(Resource)]
-struct EnemyRes {
-    ball: Handle<Image>,
+pub struct EnemyRes {
+    pub sheets: Sheets,
 }
 
 const MAX_HP: f32 = 500.;
+// the sprites used to be 200 pixels high, the StormHead frames are smaller
+const SPRITE_SCALE: f32 = 200. / 124.;
+// world units per second
+pub const SPEED: f32 = 10.;
+// how many tiles away from their heart enemies spawn
+pub const SPAWN_DISTANCE: u32 = 15;
+// world units per second
+const BOLT_SPEED: f32 = 1500.;
+
+impl EnemyBundle {
+    pub fn new(
+        res: &EnemyRes,
+        archetypes: &Archetypes,
+        pos: Vec2,
+        target: Entity,
+        path: PathfindPath,
+        hp: f32,
+        archetype: Archetype,
+    ) -> Self {
+        let def = archetypes.get(&archetype);
+        Self {
+            apperance: SpriteBundle {
+                sprite: Sprite {
+                    color: def.color(),
+                    ..default()
+                },
+                texture: res.sheets.get(Clip::Run).image.clone(),
+                transform: Transform::from_translation(pos.extend(1.)).with_scale(Vec3::splat(0.)),
+                ..default()
+            },
+            target: PathfindTarget(target),
+            path,
+            activity: EnemyActivity::default(),
+            targetable: TargetableEntity,
+            health: Health(hp),
+            dmg: Dmg(10.),
+            speed: Speed(SPEED * def.speed),
+            archetype,
+            atlas: TextureAtlas {
+                layout: res.sheets.get(Clip::Run).layout.clone(),
+                index: 0,
+            },
+            animation: Animation::new(Clip::Run),
+            seen_health: SeenHealth(hp),
+        }
+    }
+}
 
-fn load_res(mut cmd: Commands, ass: Res<AssetServer>) {
+fn load_res(
+    mut cmd: Commands,
+    ass: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
     cmd.insert_resource(EnemyRes {
-        ball: ass.load("enemy.png"),
+        sheets: Sheets::load(&ass, &mut layouts),
     });
 }
 
+// steers along the flow field of the target, the path is kept for circuits and drawing
 fn follow_path(
     map: Res<Map>,
-    mut ents: Query<(&mut Transform, &EnemyActivity, &mut PathfindPath)>,
+    fields: Res<FlowFields>,
+    mut ents: Query<(
+        &mut Transform,
+        &EnemyActivity,
+        &mut PathfindPath,
+        Option<&PathfindTarget>,
+        &Speed,
+    )>,
     time: Res<Time>,
 ) {
-    for (mut trans, activity, mut path) in ents.iter_mut() {
+    for (mut trans, activity, mut path, target, speed) in ents.iter_mut() {
         if *activity != EnemyActivity::Pathfinding {
             continue;
         };
 
-        let Some(next) = path.path.get(path.i) else {
+        if let Some(field) = target.and_then(|t| fields.0.get(&t.0)) {
+            // arrived at a tile the field leads somewhere else from
+            if path.last().is_some_and(|h| field.next(h) != path.next()) {
+                path.follow(field);
+            }
+        }
+
+        let Some(next) = path.next() else {
             // done
             continue;
         };
 
         let pos = trans.translation.xy();
-        let nextpos = map.layout.hex_to_world_pos(*next);
+        let nextpos = map.layout.hex_to_world_pos(next);
         let diff = nextpos - pos;
-        if diff.length() < 0.1 {
+        let step = speed.0 * time.delta_seconds();
+        if diff.length() <= step {
+            // arrived, fast enemies would overshoot and swing around the center
+            trans.translation = nextpos.extend(trans.translation.z);
             path.i += 1;
             continue;
         }
-        let dir = Dir2::new(diff).unwrap();
-        let speed = 10.;
 
-        trans.translation += (dir * speed).extend(0.) * time.delta_seconds();
+        trans.translation += (diff.normalize() * step).extend(0.);
     }
 }
 
@@ -130,10 +375,19 @@ fn attack(
     mut cmd: Commands,
     time: Res<Time>,
     common: Res<CommonResources>,
+    map: Res<Map>,
+    archetypes: Res<Archetypes>,
     mut health: Query<&mut Health>,
-    mut ents: Query<(&mut EnemyActivity, &Dmg)>,
+    positions: Query<&HexPos>,
+    mut ents: Query<(
+        &mut EnemyActivity,
+        &Dmg,
+        &Transform,
+        &Archetype,
+        Option<&Ranged>,
+    )>,
 ) {
-    for (mut activity, dmg) in ents.iter_mut() {
+    for (mut activity, dmg, trans, archetype, ranged) in ents.iter_mut() {
         let EnemyActivity::Attacking(e, t) = activity.as_mut() else {
             continue;
         };
@@ -153,112 +407,234 @@ fn attack(
             source: common.dmg_sound.clone(),
             ..default()
         });
+
+        if ranged.is_none() {
+            continue;
+        }
+        let from = trans.translation.xy();
+        let Ok(to) = positions.get(*e).map(|p| map.layout.hex_to_world_pos(p.0)) else {
+            continue;
+        };
+        // standing right on the target, there is nothing to fly
+        let Ok(dir) = Dir2::new(to - from) else {
+            continue;
+        };
+        cmd.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: archetypes.get(archetype).color(),
+                    custom_size: Some(Vec2::new(3., 14.)),
+                    ..default()
+                },
+                transform: Transform::from_translation(from.extend(3.))
+                    .with_rotation(Quat::from_rotation_arc(Vec3::Y, dir.extend(0.))),
+                ..default()
+            },
+            Bolt { to },
+        ));
+    }
+}
+
+// bolts fly to what they hit and vanish there
+fn fly_bolts(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut bolts: Query<(Entity, &Bolt, &mut Transform)>,
+) {
+    for (e, bolt, mut trans) in bolts.iter_mut() {
+        let pos = trans.translation.xy();
+        let step = BOLT_SPEED * time.delta_seconds();
+        if pos.distance(bolt.to) <= step {
+            cmd.entity(e).despawn();
+            continue;
+        }
+        trans.translation += ((bolt.to - pos).normalize() * step).extend(0.);
+    }
+}
+
+// the archetype specific components
+fn add_traits(
+    mut cmd: Commands,
+    archetypes: Res<Archetypes>,
+    ents: Query<(Entity, &Archetype, &Health), Added<Archetype>>,
+) {
+    for (e, archetype, hp) in ents.iter() {
+        let def = archetypes.get(archetype);
+        if let Some(range) = def.range {
+            cmd.entity(e).insert(Ranged { range });
+        }
+        if let Some(split) = &def.split {
+            cmd.entity(e).insert(Splitter {
+                children: split.children,
+                into: split.into.clone(),
+                hp: hp.0 / 2.,
+            });
+        }
+        if !def.shield.is_empty() {
+            cmd.entity(e).insert(Shield(def.shield.clone()));
+        }
     }
 }
 
-fn scale_enemy(mut ents: Query<(&Health, &mut Dmg, &mut Transform)>) {
-    for (hp, mut dmg, mut trans) in ents.iter_mut() {
-        dmg.0 = f32::max(hp.0 / 10., 1.);
-        trans.scale = Vec2::splat(hp.0 / MAX_HP / 2.).extend(0.);
+// dying enemies keep their size while the death clip plays
+fn scale_enemy(
+    archetypes: Res<Archetypes>,
+    mut ents: Query<(&Health, &mut Dmg, &mut Transform, &Archetype), Without<Dying>>,
+) {
+    for (hp, mut dmg, mut trans, archetype) in ents.iter_mut() {
+        dmg.0 = f32::max(hp.0 / 10., 1.) * archetypes.get(archetype).dmg;
+        trans.scale = Vec2::splat(hp.0 / MAX_HP / 2. * SPRITE_SCALE).extend(0.);
     }
 }
 
 fn activity_transition(
     map: Res<Map>,
     tt: Query<&TileType>,
-    mut ents: Query<(&mut EnemyActivity, &Transform)>,
+    mut ents: Query<(&mut EnemyActivity, &Transform, Option<&Ranged>)>,
 ) {
-    for (mut activity, trans) in ents.iter_mut() {
+    for (mut activity, trans, ranged) in ents.iter_mut() {
         let pos = map.layout.world_pos_to_hex(trans.translation.xy());
-        let Some(ne) = map.storage.get(pos) else {
-            continue;
-        };
 
-        let nt = tt.get(*ne).unwrap();
-        if *nt == TileType::Unoccupied {
-            activity.set_if_neq(EnemyActivity::Pathfinding);
-            continue;
-        }
+        // whatever is on the tile blocks the way, ranged enemies included
+        let blocking = map
+            .storage
+            .get(pos)
+            .and_then(|ne| match tt.get(*ne).unwrap() {
+                TileType::CyberNode { e, .. } | TileType::Terrain(e) | TileType::Heart(e) => {
+                    Some(*e)
+                }
+                TileType::Unoccupied => None,
+            });
+        let target = blocking.or_else(|| ranged.and_then(|r| in_range(&map, &tt, pos, r.range)));
 
-        let e = match nt {
-            TileType::CyberNode { e, .. } => e,
-            TileType::Terrain(e) => e,
-            TileType::Heart(e) => e,
-            _ => unreachable!(),
-        };
-
-        if !matches!(activity.as_ref(), EnemyActivity::Attacking(..)) {
-            *activity = EnemyActivity::Attacking(
-                *e,
-                Timer::new(Duration::from_secs(1), TimerMode::Repeating),
-            );
+        match target {
+            Some(e) => start_attacking(&mut activity, e),
+            None => {
+                activity.set_if_neq(EnemyActivity::Pathfinding);
+            }
         }
     }
 }
 
-// spawn in radius around hearts
+fn start_attacking(activity: &mut Mut<EnemyActivity>, e: Entity) {
+    if !matches!(activity.as_ref(), EnemyActivity::Attacking(t, _) if *t == e) {
+        **activity =
+            EnemyActivity::Attacking(e, Timer::new(Duration::from_secs(1), TimerMode::Repeating));
+    }
+}
+
+// the closest node or heart a ranged enemy at `pos` can shoot
+fn in_range(map: &Map, tt: &Query<&TileType>, pos: Hex, range: u32) -> Option<Entity> {
+    pos.range(range)
+        .filter_map(|h| {
+            let e = match tt.get(*map.storage.get(h)?).ok()? {
+                TileType::CyberNode { e, .. } | TileType::Heart(e) => *e,
+                _ => return None,
+            };
+            Some((pos.unsigned_distance_to(h), e))
+        })
+        .min_by_key(|(dist, _)| *dist)
+        .map(|(_, e)| e)
+}
+
+// spawn in radius around hearts, as the wave schedule says
 fn spawner(
     mut cmd: Commands,
     mut ticks: EventReader<Tick>,
     res: Res<EnemyRes>,
+    archetypes: Res<Archetypes>,
     map: Res<Map>,
-    types: Query<&TileType>,
+    fields: Res<FlowFields>,
     hearts: Query<&HexPos>,
+    mut rng: ResMut<SimRng>,
+    waves: Option<Res<Waves>>,
+    mut director: ResMut<WaveDirector>,
 ) {
-    for t in ticks.read() {
-        // 1/3 chance to spawn enemy
-        if !rand::thread_rng().gen_bool(1. / 3.) {
-            continue;
-        }
+    let Some(waves) = waves else {
+        // schedule not loaded yet
+        return;
+    };
 
+    for t in ticks.read() {
         let Ok(heart) = hearts.get(t.0) else {
             // heart was propaby destroyed
             continue;
         };
 
-        let mut rng = rand::thread_rng();
-        let spawntile = heart.0.ring(15).choose(&mut rng).unwrap();
+        let Some((archetype, hp)) = director.beat(&waves.0, &mut rng.0) else {
+            continue;
+        };
+
+        let spawntile = heart.0.ring(SPAWN_DISTANCE).choose(&mut rng.0).unwrap();
         let spawnpos = map.layout.hex_to_world_pos(spawntile);
-        let Some(path) = algorithms::a_star(spawntile, heart.0, |_, h2| {
-            let Some(e2) = map.storage.get(h2) else {
-                return Some(0);
-            };
-            let t2 = types.get(*e2).unwrap();
-            match t2 {
-                TileType::Unoccupied => Some(0),
-                TileType::Heart(_) => Some(1),
-                TileType::Terrain(_) => Some(10),
-                TileType::CyberNode { .. } => Some(50),
-            }
-        }) else {
+        let Some(path) = fields.0.get(&t.0).and_then(|f| f.path(spawntile)) else {
             // no path to target
-            return;
+            continue;
         };
 
-        cmd.spawn(EnemyBundle {
-            apperance: SpriteBundle {
-                sprite: Sprite {
-                    color: Color::srgb(5., 1., 1.),
-                    ..default()
-                },
-                texture: res.ball.clone(),
-                transform: Transform::from_translation(spawnpos.extend(1.))
-                    .with_scale(Vec3::splat(0.)),
-                ..default()
-            },
-            target: PathfindTarget(t.0),
-            path: PathfindPath { path, i: 0 },
-            activity: EnemyActivity::default(),
-            targetable: TargetableEntity,
-            health: Health(rng.gen_range(10..MAX_HP as u32) as f32),
-            dmg: Dmg(10.),
-        });
+        let hp = hp * archetypes.get(&archetype).hp;
+        cmd.spawn(EnemyBundle::new(
+            &res,
+            &archetypes,
+            spawnpos,
+            t.0,
+            PathfindPath { path, i: 0 },
+            hp,
+            archetype,
+        ));
     }
 }
 
-fn despawn(mut cmd: Commands, hp: Query<(Entity, &Health)>) {
-    for (e, hp) in hp.iter() {
+// stops being a target and plays the death clip, splitters split right away
+fn die(
+    mut cmd: Commands,
+    res: Res<EnemyRes>,
+    archetypes: Res<Archetypes>,
+    mut hp: Query<(Entity, &Health, Option<&mut Animation>), With<TargetableEntity>>,
+    splitters: Query<(&Splitter, &Transform, &PathfindPath, &PathfindTarget)>,
+    mut killed: EventWriter<EnemyKilled>,
+) {
+    for (e, hp, anim) in hp.iter_mut() {
         if hp.0 <= 0. {
+            killed.send(EnemyKilled);
+            match anim {
+                Some(mut anim) => {
+                    anim.play(Clip::Death);
+                    cmd.entity(e)
+                        .remove::<(TargetableEntity, EnemyActivity)>()
+                        .insert(Dying);
+                }
+                // nothing to animate
+                None => cmd.entity(e).despawn_recursive(),
+            }
+
+            let Ok((split, trans, path, target)) = splitters.get(e) else {
+                continue;
+            };
+            for i in 0..split.children {
+                // spread out a little so they don't overlap
+                let offset = Vec2::from_angle(i as f32 * TAU / split.children as f32) * 5.;
+                cmd.spawn(EnemyBundle::new(
+                    &res,
+                    &archetypes,
+                    trans.translation.xy() + offset,
+                    target.0,
+                    PathfindPath {
+                        path: path.path.clone(),
+                        i: path.i,
+                    },
+                    split.hp * archetypes.get(&split.into).hp,
+                    split.into.clone(),
+                ));
+            }
+        }
+    }
+}
+
+// once the death clip played, tiles are cleaned up by destroy_nodes
+fn despawn(mut cmd: Commands, ents: Query<(Entity, &Animation), With<Dying>>) {
+    for (e, anim) in ents.iter() {
+        if anim.finished() {
             cmd.entity(e).despawn_recursive();
         }
     }
@@ -0,0 +1,578 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    asset::{io::file::FileAssetReader, AssetLoadFailedEvent},
+    audio::AudioLoader,
+    ecs::schedule::ExecutorKind,
+    gizmos::GizmoPlugin,
+    log::{Level, LogPlugin},
+    prelude::*,
+    render::render_resource::Shader,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+use hexx::Hex;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    blueprint::{self, Blueprint},
+    catalog::{NodeCatalog, NodeItems, CATALOG_PATH},
+    economy::{Credits, Price},
+    enemy::{Archetypes, EnemyBundle, EnemyKilled, EnemyRes, PathfindPath, SPAWN_DISTANCE},
+    nodes::{
+        CyberState, Health, HexPos, MetaLink, NodeKind, PortCfg, PortMeta, PortMetas,
+        TargetableEntity, Val,
+    },
+    pathing::{PathCosts, COSTS_PATH},
+    save::{self, LoadGame, SaveSettings},
+    shop::PickedItem,
+    waves::{WaveSchedule, Waves, WAVES_PATH},
+    Appstate, Heartbeat, Map, Rip, SimPlugin, SimRng, Tick, TileType,
+};
+
+// one update advances exactly one fixed timestep
+const STEP: Duration = Duration::from_micros(15_625);
+
+// give up if the game's assets did not load by then
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+// enemy counts the benchmark measures by default
+const BENCH_ENEMIES: [usize; 3] = [100, 500, 1000];
+// updates before measuring, so the flow fields are built
+const BENCH_WARMUP: u32 = 10;
+// enough that no benchmark enemy dies
+const BENCH_HP: f32 = 1_000_000.;
+
+// the engine parts the simulation needs, without a window, renderer or audio output
+pub struct HeadlessPlugin {
+    // the directory assets are read from
+    pub assets: String,
+}
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(StatesPlugin)
+            .add_plugins(AssetPlugin {
+                file_path: self.assets.clone(),
+                ..default()
+            })
+            .add_plugins(ImagePlugin::default())
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_asset::<AudioSource>()
+            .init_asset::<TextureAtlasLayout>()
+            .init_asset_loader::<AudioLoader>()
+            // gizmos want their shaders even if nothing is drawn
+            .init_asset::<Shader>()
+            .add_plugins(GizmoPlugin)
+            .insert_resource(Time::<Fixed>::from_duration(STEP))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(STEP))
+            .init_resource::<SimStats>()
+            // nothing is written to disk unless asked for
+            .insert_resource(SaveSettings { autosave: None })
+            .add_systems(Last, record_stats);
+
+        // parallel systems would reserve entities in a different order every run
+        let single_threaded = |s: &mut Schedule| {
+            s.set_executor_kind(ExecutorKind::SingleThreaded);
+        };
+        app.edit_schedule(PreUpdate, single_threaded)
+            .edit_schedule(Update, single_threaded)
+            .edit_schedule(FixedUpdate, single_threaded);
+    }
+}
+
+#[derive(Resource, Default)]
+struct SimStats {
+    heartbeats: u32,
+    enemies_killed: u32,
+    heart_destroyed: bool,
+}
+
+fn record_stats(
+    mut stats: ResMut<SimStats>,
+    mut tick: EventReader<Tick>,
+    mut killed: EventReader<EnemyKilled>,
+    mut rip: EventReader<Rip>,
+) {
+    stats.heartbeats += tick.read().count() as u32;
+    stats.enemies_killed += killed.read().count() as u32;
+    if rip.read().next().is_some() {
+        stats.heart_destroyed = true;
+    }
+}
+
+fn load_error<A: Asset>(world: &World) -> Option<String> {
+    let events = world.resource::<Events<AssetLoadFailedEvent<A>>>();
+    let e = events.get_reader().read(events).next()?;
+    Some(format!("could not load {}: {}", e.path, e.error))
+}
+
+pub struct NodeReport {
+    pub tile: Hex,
+    pub node: NodeKind,
+    pub state: CyberState,
+}
+
+pub struct SimReport {
+    pub heartbeats: u32,
+    // None once the heart is destroyed
+    pub heart_hp: Option<f32>,
+    pub enemies_killed: u32,
+    pub enemies_alive: usize,
+    pub credits: u32,
+    pub nodes: Vec<NodeReport>,
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "heartbeats: {}", self.heartbeats)?;
+        match self.heart_hp {
+            Some(hp) => writeln!(f, "heart hp: {}", hp)?,
+            None => writeln!(f, "heart hp: destroyed")?,
+        }
+        writeln!(f, "enemies killed: {}", self.enemies_killed)?;
+        writeln!(f, "enemies alive: {}", self.enemies_alive)?;
+        writeln!(f, "credits: {}", self.credits)?;
+        writeln!(f, "nodes:")?;
+        for n in self.nodes.iter() {
+            writeln!(
+                f,
+                "  ({}, {}) {}: {:?}",
+                n.tile.x, n.tile.y, n.node.0, n.state
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct BenchReport {
+    pub enemies: usize,
+    pub frames: Vec<Duration>,
+}
+
+impl BenchReport {
+    fn ms(d: Duration) -> f64 {
+        d.as_secs_f64() * 1000.
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.frames.iter().sum::<Duration>() / self.frames.len().max(1) as u32
+    }
+
+    // the frame time `p` percent of the frames are faster than
+    pub fn percentile(&self, p: usize) -> Duration {
+        let mut sorted = self.frames.clone();
+        sorted.sort();
+        let i = (sorted.len() * p / 100).min(sorted.len().saturating_sub(1));
+        sorted.get(i).copied().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "enemies: {}", self.enemies)?;
+        writeln!(f, "  frames: {}", self.frames.len())?;
+        writeln!(f, "  mean: {:.3}ms", Self::ms(self.mean()))?;
+        writeln!(f, "  p95: {:.3}ms", Self::ms(self.percentile(95)))?;
+        writeln!(f, "  max: {:.3}ms", Self::ms(self.percentile(100)))
+    }
+}
+
+// a game without a window that is stepped by hand.
+// it plays with the game's own node catalog, wave schedule and path costs,
+// so the assets directory has to be there, unless others are given with `with_assets`
+pub struct Sim {
+    pub app: App,
+}
+
+impl Sim {
+    pub fn new(seed: u64) -> Self {
+        let mut app = App::new();
+        app.add_plugins(LogPlugin {
+            level: Level::WARN,
+            ..default()
+        });
+        Self::with_app(app, seed)
+    }
+
+    // `app` may come with extra plugins, like logging
+    pub fn with_app(app: App, seed: u64) -> Self {
+        Self::with_assets(app, seed, AssetPlugin::default().file_path)
+    }
+
+    // reads the node catalog, wave schedule and path costs from `assets` instead
+    pub fn with_assets(mut app: App, seed: u64, assets: impl Into<String>) -> Self {
+        let assets = assets.into();
+        app.add_plugins(HeadlessPlugin {
+            assets: assets.clone(),
+        })
+        .add_plugins(SimPlugin)
+        .insert_resource(SimRng(StdRng::seed_from_u64(seed)));
+        app.finish();
+        app.cleanup();
+
+        let dir = FileAssetReader::get_base_path().join(&assets);
+        for path in [CATALOG_PATH, WAVES_PATH, COSTS_PATH] {
+            let path = dir.join(path);
+            assert!(
+                path.exists(),
+                "{} is missing, the sim needs it in the {:?} directory",
+                path.display(),
+                assets
+            );
+        }
+
+        // the assets load in the background, the shop items are needed to place nodes
+        let start = Instant::now();
+        while app.world().resource::<NodeItems>().order.is_empty()
+            || !app.world().contains_resource::<Waves>()
+            || !app.world().contains_resource::<PathCosts>()
+        {
+            let world = app.world();
+            if let Some(e) = load_error::<NodeCatalog>(world)
+                .or_else(|| load_error::<WaveSchedule>(world))
+                .or_else(|| load_error::<PathCosts>(world))
+            {
+                panic!("{}", e);
+            }
+            if start.elapsed() > LOAD_TIMEOUT {
+                panic!(
+                    "node catalog, wave schedule or path costs did not load within {:?}",
+                    LOAD_TIMEOUT
+                );
+            }
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        app.world_mut()
+            .resource_mut::<NextState<Appstate>>()
+            .set(Appstate::Game);
+        app.update();
+
+        Self { app }
+    }
+
+    pub fn step(&mut self) {
+        self.app.update();
+    }
+
+    fn item(&self, node: &str) -> Entity {
+        *self
+            .app
+            .world()
+            .resource::<NodeItems>()
+            .by_node
+            .get(&NodeKind(node.to_string()))
+            .unwrap_or_else(|| panic!("no node {:?} in the catalog", node))
+    }
+
+    // places a node like buying it in the shop would, its price is paid for by the sim
+    pub fn place(&mut self, node: &str, tile: Hex) -> Entity {
+        let price = self.app.world().get::<Price>(self.item(node)).copied();
+        self.app.world_mut().resource_mut::<Credits>().0 += price.unwrap_or_default().0;
+        self.buy(node, tile)
+            .unwrap_or_else(|| panic!("could not place {:?} at {:?}", node, tile))
+    }
+
+    // buys a node with the credits there are, `None` if it could not be placed
+    pub fn buy(&mut self, node: &str, tile: Hex) -> Option<Entity> {
+        let item = self.item(node);
+        self.app.world_mut().send_event(PickedItem { item, tile });
+        self.step();
+        self.node_at(tile)
+    }
+
+    // spreads `n` enemies of every archetype over the ring enemies spawn on
+    pub fn spawn_enemies(&mut self, n: usize, hp: f32) {
+        let world = self.app.world_mut();
+        let (heart, pos) = world
+            .query_filtered::<(Entity, &HexPos), With<Heartbeat>>()
+            .single(world);
+        let tiles: Vec<Hex> = pos.0.ring(SPAWN_DISTANCE).collect();
+
+        let res = world.resource::<EnemyRes>();
+        let archetypes = world.resource::<Archetypes>();
+        let names = archetypes.names();
+        let layout = &world.resource::<Map>().layout;
+        let enemies: Vec<EnemyBundle> = (0..n)
+            .map(|i| {
+                let tile = tiles[i % tiles.len()];
+                let archetype = names.get(i % names.len().max(1)).cloned();
+                EnemyBundle::new(
+                    res,
+                    archetypes,
+                    layout.hex_to_world_pos(tile),
+                    heart,
+                    PathfindPath {
+                        path: vec![tile],
+                        i: 0,
+                    },
+                    hp,
+                    archetype.unwrap_or_default(),
+                )
+            })
+            .collect();
+        world.spawn_batch(enemies);
+    }
+
+    // steps `frames` times, timing every update
+    pub fn time_steps(&mut self, frames: u32) -> Vec<Duration> {
+        (0..frames)
+            .map(|_| {
+                let start = Instant::now();
+                self.step();
+                start.elapsed()
+            })
+            .collect()
+    }
+
+    pub fn node_at(&self, tile: Hex) -> Option<Entity> {
+        let world = self.app.world();
+        let te = world.resource::<Map>().storage.get(tile)?;
+        match world.get::<TileType>(*te)? {
+            TileType::CyberNode { e, .. } => Some(*e),
+            _ => None,
+        }
+    }
+
+    // wires the port `port` of the node at `tile` to the node at `from`
+    pub fn connect(&mut self, tile: Hex, port: &str, from: Hex) {
+        let e = self.node_at(tile).expect("no node to connect");
+        let world = self.app.world_mut();
+        let meta = world.get::<MetaLink>(e).unwrap().0;
+        let pe = world
+            .get::<PortMetas>(meta)
+            .unwrap()
+            .0
+            .iter()
+            .copied()
+            .find(|pe| world.get::<PortMeta>(*pe).unwrap().name == port)
+            .unwrap_or_else(|| panic!("node has no port {:?}", port));
+
+        let mut cfg = world.get_mut::<PortCfg>(e).unwrap();
+        cfg.inputs.retain(|_, p| *p != pe);
+        cfg.inputs.insert(from - tile, pe);
+    }
+
+    pub fn set_constant(&mut self, tile: Hex, v: Val) {
+        let e = self.node_at(tile).expect("no node to configure");
+        self.app.world_mut().get_mut::<PortCfg>(e).unwrap().constant = Some(v);
+    }
+
+    // writes right away instead of through SaveGame, which is only read while the game runs.
+    // that way a board whose heart was destroyed can still be saved and looked at
+    pub fn save(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        if let Err(e) = save::save(self.app.world_mut(), &path) {
+            panic!("could not save game to {:?}: {}", path, e);
+        }
+    }
+
+    // replaces the board, nodes placed before are gone
+    pub fn load(&mut self, path: impl Into<PathBuf>) {
+        self.app.world_mut().send_event(LoadGame(path.into()));
+        self.step();
+    }
+
+    pub fn copy(&self, center: Hex, radius: u32) -> Blueprint {
+        blueprint::capture(self.app.world(), center, radius)
+    }
+
+    // returns the number of placed nodes, occupied tiles are skipped
+    pub fn paste(&mut self, bp: &Blueprint, center: Hex) -> usize {
+        let placed = blueprint::paste(self.app.world_mut(), bp, center);
+        self.step();
+        placed
+    }
+
+    // steps until `n` more heartbeats happened or the heart is destroyed
+    pub fn heartbeats(&mut self, n: u32) -> SimReport {
+        let target = self.stats().heartbeats + n;
+        while self.stats().heartbeats < target && !self.stats().heart_destroyed {
+            self.step();
+        }
+
+        // stepping on would leave the game and clean up the map
+        if !self.stats().heart_destroyed {
+            self.settle();
+        }
+        self.report()
+    }
+
+    // a heartbeat evaluates the whole graph in one step, unless the debugger holds it back.
+    // steps until the node states stop changing, so pending ticks and changed tiles are taken in
+    pub fn settle(&mut self) {
+        let mut last = self.node_states();
+        for _ in 0..64 {
+            self.step();
+            let states = self.node_states();
+            if states == last {
+                return;
+            }
+            last = states;
+        }
+    }
+
+    fn node_states(&mut self) -> Vec<CyberState> {
+        self.app
+            .world_mut()
+            .query::<&CyberState>()
+            .iter(self.app.world())
+            .cloned()
+            .collect()
+    }
+
+    fn stats(&self) -> &SimStats {
+        self.app.world().resource::<SimStats>()
+    }
+
+    pub fn report(&mut self) -> SimReport {
+        let world = self.app.world_mut();
+        let heart_hp = world
+            .query_filtered::<&Health, With<Heartbeat>>()
+            .iter(world)
+            .next()
+            .map(|hp| hp.0);
+        let enemies_alive = world
+            .query_filtered::<(), With<TargetableEntity>>()
+            .iter(world)
+            .count();
+
+        let placed: Vec<_> = world
+            .query::<&TileType>()
+            .iter(world)
+            .filter_map(|tt| match tt {
+                TileType::CyberNode { meta, e } => Some((*meta, *e)),
+                _ => None,
+            })
+            .collect();
+        let mut nodes: Vec<_> = placed
+            .into_iter()
+            .filter_map(|(meta, e)| {
+                Some(NodeReport {
+                    tile: world.get::<HexPos>(e)?.0,
+                    node: world.get::<NodeKind>(meta)?.clone(),
+                    state: world.get::<CyberState>(e)?.clone(),
+                })
+            })
+            .collect();
+        nodes.sort_by_key(|n| (n.tile.x, n.tile.y));
+
+        let stats = world.resource::<SimStats>();
+        SimReport {
+            heartbeats: stats.heartbeats,
+            heart_hp,
+            enemies_killed: stats.enemies_killed,
+            enemies_alive,
+            credits: world.resource::<Credits>().0,
+            nodes,
+        }
+    }
+}
+
+fn arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1)?.parse().ok()
+}
+
+// all values following each occurence of `name`
+fn args_of<'a>(args: &'a [String], name: &'a str) -> impl Iterator<Item = &'a str> {
+    args.windows(2)
+        .filter(move |w| w[0] == name)
+        .map(|w| w[1].as_str())
+}
+
+fn parse_hex(s: &str) -> Hex {
+    let parsed = s
+        .split_once(',')
+        .and_then(|(x, y)| Some(Hex::new(x.parse().ok()?, y.parse().ok()?)));
+    parsed.unwrap_or_else(|| panic!("invalid tile {:?}, expected x,y", s))
+}
+
+// `cyberspace --headless [heartbeats] [--seed n] [--load file] [--save file]`
+// the save is loaded before placing and written after the run, even if the heart was destroyed.
+// nodes are placed in order, so place them outwards from the heart:
+//   --place <node>:<x,y>
+//   --blueprint <file>:<x,y>[:rotation]  pastes an exported blueprint around the tile
+//   --connect <x,y>:<port>:<x,y>  wires the port of the first node to the second
+//   --constant <x,y>:<number>
+pub fn run(args: &[String]) {
+    let heartbeats = arg(args, "--headless").unwrap_or(100);
+    let seed = arg(args, "--seed").unwrap_or(0);
+
+    let mut sim = Sim::new(seed);
+    if let Some(path) = arg::<PathBuf>(args, "--load") {
+        sim.load(path);
+    }
+
+    for place in args_of(args, "--place") {
+        let (node, tile) = place.split_once(':').expect("expected <node>:<x,y>");
+        sim.place(node, parse_hex(tile));
+    }
+
+    for paste in args_of(args, "--blueprint") {
+        let mut parts = paste.split(':');
+        let (Some(file), Some(tile)) = (parts.next(), parts.next()) else {
+            panic!("expected <file>:<x,y>[:rotation]");
+        };
+        let rotation = parts
+            .next()
+            .map_or(0, |r| r.parse().expect("invalid rotation"));
+        let bp = Blueprint::import(Path::new(file))
+            .unwrap_or_else(|e| panic!("could not import blueprint {:?}: {}", file, e));
+        sim.paste(&bp.rotated(rotation), parse_hex(tile));
+    }
+
+    for connect in args_of(args, "--connect") {
+        let mut parts = connect.splitn(3, ':');
+        let (Some(tile), Some(port), Some(from)) = (parts.next(), parts.next(), parts.next())
+        else {
+            panic!("expected <x,y>:<port>:<x,y>");
+        };
+        sim.connect(parse_hex(tile), port, parse_hex(from));
+    }
+
+    for constant in args_of(args, "--constant") {
+        let (tile, v) = constant.split_once(':').expect("expected <x,y>:<number>");
+        let v = v.parse().expect("constant is not a number");
+        sim.set_constant(parse_hex(tile), Val::Number(v));
+    }
+
+    let report = sim.heartbeats(heartbeats);
+    print!("{}", report);
+
+    if let Some(path) = arg::<PathBuf>(args, "--save") {
+        sim.save(path);
+    }
+}
+
+// `cyberspace --bench [frames] [--seed n] [--enemies n]...`
+// measures the frame time with 100, 500 and 1000 enemies walking to the heart,
+// or with the given counts. nothing kills them and nothing is rendered.
+pub fn bench(args: &[String]) {
+    let frames = arg(args, "--bench").unwrap_or(600);
+    let seed = arg(args, "--seed").unwrap_or(0);
+    let mut counts: Vec<usize> = args_of(args, "--enemies")
+        .map(|n| n.parse().expect("enemy count is not a number"))
+        .collect();
+    if counts.is_empty() {
+        counts = BENCH_ENEMIES.to_vec();
+    }
+
+    for enemies in counts {
+        let mut sim = Sim::with_app(App::new(), seed);
+        sim.spawn_enemies(enemies, BENCH_HP);
+        sim.time_steps(BENCH_WARMUP);
+        let report = BenchReport {
+            enemies,
+            frames: sim.time_steps(frames),
+        };
+        print!("{}", report);
+    }
+}
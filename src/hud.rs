@@ -1,6 +1,13 @@
 use bevy::prelude::*;
 
-use crate::{nodes::Health, ui::UIRoot, Gamestate, Heartbeat, Map, Selection};
+use crate::{
+    economy::Credits,
+    nodes::{CyberState, Health},
+    scheduler::Loops,
+    ui::UIRoot,
+    waves::WaveDirector,
+    Gamestate, Heartbeat, Map, Selection, TileType,
+};
 
 pub struct HudPlugin;
 impl Plugin for HudPlugin {
@@ -10,7 +17,16 @@ impl Plugin for HudPlugin {
             .add_systems(OnExit(Gamestate::Game), hide)
             .add_systems(
                 FixedUpdate,
-                (update_cycles, update_energy, update_pos, update_tile)
+                (
+                    update_cycles,
+                    update_credits,
+                    update_wave,
+                    update_energy,
+                    update_pos,
+                    update_tile,
+                    update_node,
+                    update_loops,
+                )
                     .run_if(in_state(Gamestate::Game)),
             );
     }
@@ -25,12 +41,27 @@ struct CpuEnergy;
 #[derive(Component)]
 struct CpuCycles;
 
+#[derive(Component)]
+struct CreditCount;
+
+#[derive(Component)]
+struct WaveNumber;
+
+#[derive(Component)]
+struct WaveCountdown;
+
 #[derive(Component)]
 struct HoverPos;
 
 #[derive(Component)]
 struct HoverTile;
 
+#[derive(Component)]
+struct HoverNode;
+
+#[derive(Component)]
+struct LoopTiles;
+
 fn setup(mut cmd: Commands, root: Res<UIRoot>, ass: Res<AssetServer>) {
     let textstyle = TextStyle {
         font: ass.load("fonts/Geist-Regular.ttf"),
@@ -68,6 +99,30 @@ fn setup(mut cmd: Commands, root: Res<UIRoot>, ass: Res<AssetServer>) {
                 CpuCycles,
             ));
 
+            hud.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("credits: ", textstyle.clone()),
+                    TextSection::new("", textstyle.clone()),
+                ]),
+                CreditCount,
+            ));
+
+            hud.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("wave: ", textstyle.clone()),
+                    TextSection::new("", textstyle.clone()),
+                ]),
+                WaveNumber,
+            ));
+
+            hud.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("next wave: ", textstyle.clone()),
+                    TextSection::new("", textstyle.clone()),
+                ]),
+                WaveCountdown,
+            ));
+
             hud.spawn((
                 TextBundle::from_sections([
                     TextSection::new("pos: ", textstyle.clone()),
@@ -83,6 +138,22 @@ fn setup(mut cmd: Commands, root: Res<UIRoot>, ass: Res<AssetServer>) {
                 ]),
                 HoverTile,
             ));
+
+            hud.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("node: ", textstyle.clone()),
+                    TextSection::new("", textstyle.clone()),
+                ]),
+                HoverNode,
+            ));
+
+            hud.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("loops: ", textstyle.clone()),
+                    TextSection::new("", textstyle.clone()),
+                ]),
+                LoopTiles,
+            ));
         })
         .id();
 
@@ -104,6 +175,32 @@ fn update_cycles(mut text: Query<&mut Text, With<CpuCycles>>, hp: Query<&Heartbe
     }
 }
 
+fn update_credits(mut text: Query<&mut Text, With<CreditCount>>, credits: Res<Credits>) {
+    for mut text in text.iter_mut() {
+        text.sections[1].value = credits.0.to_string();
+    }
+}
+
+// the countdown is in heartbeats
+fn update_wave(
+    mut number: Query<&mut Text, (With<WaveNumber>, Without<WaveCountdown>)>,
+    mut countdown: Query<&mut Text, (With<WaveCountdown>, Without<WaveNumber>)>,
+    director: Res<WaveDirector>,
+) {
+    for mut text in number.iter_mut() {
+        text.sections[1].value = director.wave.to_string();
+    }
+
+    let next = match director.countdown {
+        _ if director.spawning() => "incoming".to_string(),
+        Some(n) => n.to_string(),
+        None => String::new(),
+    };
+    for mut text in countdown.iter_mut() {
+        text.sections[1].value = next.clone();
+    }
+}
+
 fn update_tile(mut text: Query<&mut Text, With<HoverTile>>, selection: Res<Selection>) {
     for mut text in text.iter_mut() {
         if let Some(mouse) = selection.mouseover {
@@ -112,6 +209,50 @@ fn update_tile(mut text: Query<&mut Text, With<HoverTile>>, selection: Res<Selec
     }
 }
 
+// the last result of the hovered node
+fn update_node(
+    mut text: Query<&mut Text, With<HoverNode>>,
+    selection: Res<Selection>,
+    map: Res<Map>,
+    tiles: Query<&TileType>,
+    states: Query<&CyberState>,
+) {
+    let state = selection
+        .mouseover
+        .and_then(|h| map.storage.get(h))
+        .and_then(|te| match tiles.get(*te) {
+            Ok(TileType::CyberNode { e, .. }) => states.get(*e).ok(),
+            _ => None,
+        });
+
+    let value = match state {
+        None => String::new(),
+        Some(CyberState::Done(Ok(v))) => format!("{:?}", v),
+        Some(CyberState::Done(Err(e))) => format!("error: {}", e),
+        Some(CyberState::Skipped) => "skipped".to_string(),
+        Some(s) => format!("{:?}", s),
+    };
+    for mut text in text.iter_mut() {
+        text.sections[1].value = value.clone();
+    }
+}
+
+// nodes wired in a loop fail every heartbeat until a storage breaks it up
+fn update_loops(mut text: Query<&mut Text, With<LoopTiles>>, loops: Res<Loops>) {
+    let tiles = match loops.0.is_empty() {
+        true => "none".to_string(),
+        false => loops
+            .0
+            .iter()
+            .map(|h| format!("({}, {})", h.x, h.y))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    for mut text in text.iter_mut() {
+        text.sections[1].value = tiles.clone();
+    }
+}
+
 fn update_pos(
     mut text: Query<&mut Text, With<HoverPos>>,
     selection: Res<Selection>,
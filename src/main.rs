@@ -1,21 +1,46 @@
+mod animation;
+mod blueprint;
+mod catalog;
 mod configurate;
 mod death;
+mod debugger;
+mod economy;
 mod enemy;
+mod headless;
 mod hud;
 mod nodes;
+mod overlay;
+mod pathing;
+mod registry;
+mod ron_asset;
+mod save;
+mod scheduler;
 mod shop;
+#[cfg(test)]
+mod tests;
 mod ui;
+mod waves;
 use std::{collections::HashSet, time::Duration};
 
-use enemy::EnemyPlugin;
+use blueprint::{blueprint_keys, draw_copy_region, Clipboard};
+use catalog::CatalogPlugin;
+use debugger::{
+    debugger_keys, end_step, not_typing, sim_running, spawn_debugger_panel, step_heartbeat,
+    update_debugger_panel, Debugger,
+};
+use economy::{income, Credits, Price};
+use enemy::{Dying, EnemyPlugin};
 use nodes::{
-    ClosestEntity, ConstantNumber, CyberNodes, CyberPlugin, CyberState, EntityDirection, EntityPos,
-    Health, HexPos, Lazor, List, ListLen, MetaLink, NearbyEntity, NodeBundle, NumberMul, NumberSub,
-    Orbital, Plasma, PortCfg, Project, RocketLauncher, Shock, Storage, TargetableEntity, TickNode,
-    Vector, VectorLen, VectorMul, VectorNeg,
+    CyberPlugin, CyberState, Health, HexPos, NodeBundle, OutputTypes, PortCfg, PortMeta,
+    TargetableEntity, WiringErrors, NODE_HP,
 };
+use overlay::{draw_node_overlay, overlay_keys, sync_icon_labels, sync_overlay_labels};
+use pathing::PathingPlugin;
+use save::{quicksave_keys, SavePlugin};
+use scheduler::{evaluate_heartbeats, Evaluation, Heartbeats, Loops};
 use shop::PickedItem;
 use ui::UIPlugin;
+use waves::{WaveDirector, WavePlugin};
 
 use bevy::{
     color::palettes::css::RED,
@@ -31,25 +56,25 @@ use bevy::{
 };
 use bevy_inspector_egui::{prelude::*, quick::ResourceInspectorPlugin};
 use hexx::{storage::HexagonalMap, Hex, HexLayout, PlaneMeshBuilder};
+use rand::{rngs::StdRng, SeedableRng};
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--headless") {
+        headless::run(&args);
+        return;
+    }
+    if args.iter().any(|a| a == "--bench") {
+        headless::bench(&args);
+        return;
+    }
+
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
+        .add_plugins(SimPlugin)
+        .add_systems(Startup, (spawn_camera, spawn_debugger_panel))
         .add_plugins(UIPlugin)
-        .add_plugins(CyberPlugin)
-        .add_plugins(EnemyPlugin)
         //.add_plugins(ResourceInspectorPlugin::<Debug>::default())
-        .add_systems(
-            FixedUpdate,
-            (heartbeat, tick_nodes, request_nodes, heal_heart).run_if(in_state(Gamestate::Game)),
-        )
-        .add_systems(OnEnter(Appstate::Game), init)
-        .add_systems(OnExit(Appstate::Game), deinit)
-        .add_systems(
-            PreUpdate,
-            (tile_purchased, remove_node, destroy_nodes).run_if(in_state(Gamestate::Game)),
-        )
         .add_systems(
             Update,
             (
@@ -58,26 +83,95 @@ fn main() {
                 open_shop,
                 open_configurator,
                 zoom_scale,
-                detect_changes,
-                disable_disconnected,
-                open_death,
+                (quicksave_keys, blueprint_keys, overlay_keys).run_if(not_typing),
+                draw_copy_region,
+                draw_wiring_errors,
+                draw_node_overlay,
+                sync_overlay_labels,
+                sync_icon_labels,
+                debugger_keys,
+                update_debugger_panel,
             )
                 .run_if(in_state(Gamestate::Game)),
         )
-        .add_event::<Rip>()
-        .add_event::<TileClicked>()
-        .add_event::<TileChanged>()
-        .add_event::<Tick>()
         .insert_resource(Msaa::Sample8)
-        .init_resource::<Debug>()
         .init_resource::<ShoppingForTile>()
         .init_resource::<ConfiguringTile>()
         .init_resource::<Selection>()
-        .init_state::<Appstate>()
-        .add_sub_state::<Gamestate>()
+        .init_resource::<Clipboard>()
         .run();
 }
 
+// the order of a fixed step, explicit so the same seed always plays out the same
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimSet {
+    Heartbeat,
+    Nodes,
+    Enemies,
+}
+
+// everything needed to simulate the game, without any window or input
+pub struct SimPlugin;
+impl Plugin for SimPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_plugins(CatalogPlugin)
+            .add_plugins(CyberPlugin)
+            .add_plugins(EnemyPlugin)
+            .add_plugins(PathingPlugin)
+            .add_plugins(SavePlugin)
+            .add_plugins(WavePlugin)
+            .configure_sets(
+                FixedUpdate,
+                (SimSet::Heartbeat, SimSet::Nodes, SimSet::Enemies)
+                    .chain()
+                    .run_if(in_state(Gamestate::Game))
+                    .run_if(sim_running),
+            )
+            .add_systems(
+                FixedUpdate,
+                (heal_heart, heartbeat, step_heartbeat)
+                    .chain()
+                    .in_set(SimSet::Heartbeat),
+            )
+            .add_systems(FixedUpdate, evaluate_heartbeats.in_set(SimSet::Nodes))
+            .add_systems(FixedUpdate, income.in_set(SimSet::Enemies))
+            .add_systems(FixedUpdate, end_step.after(SimSet::Enemies))
+            .add_systems(OnEnter(Appstate::Game), init)
+            .add_systems(OnExit(Appstate::Game), deinit)
+            .add_systems(
+                PreUpdate,
+                (tile_purchased, remove_node, destroy_nodes)
+                    .chain()
+                    .run_if(in_state(Gamestate::Game)),
+            )
+            .add_systems(
+                Update,
+                (
+                    detect_changes,
+                    disable_disconnected,
+                    check_wiring,
+                    open_death,
+                )
+                    .chain()
+                    .run_if(in_state(Gamestate::Game)),
+            )
+            .add_event::<Rip>()
+            .add_event::<TileClicked>()
+            .add_event::<TileChanged>()
+            .add_event::<Tick>()
+            .add_event::<PickedItem>()
+            .insert_resource(SimRng(StdRng::from_entropy()))
+            .init_resource::<Debug>()
+            .init_resource::<Debugger>()
+            .init_resource::<Evaluation>()
+            .init_resource::<Loops>()
+            .init_resource::<Credits>()
+            .init_state::<Appstate>()
+            .add_sub_state::<Gamestate>();
+    }
+}
+
 const HEX_SIZE: Vec2 = Vec2::splat(35.);
 
 #[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash, Reflect)]
@@ -108,6 +202,7 @@ struct Debug {
     mouse_cfg_tile: IVec2,
     gui_outline: bool,
     enemy_paths: bool,
+    node_values: bool,
 }
 
 #[derive(Resource, Default)]
@@ -153,6 +248,10 @@ impl Map {
 #[derive(Event)]
 struct Rip;
 
+// all gameplay randomness goes through here so headless runs can be seeded
+#[derive(Resource)]
+pub struct SimRng(pub StdRng);
+
 #[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
 enum TileType {
     #[default]
@@ -208,13 +307,14 @@ struct CommonResources {
     death_sound: Handle<AudioSource>,
     tick_sound: Handle<AudioSource>,
     boot_sound: Handle<AudioSource>,
+    font: Handle<Font>,
 }
 
 fn deinit(
     mut cmd: Commands,
     map: Res<Map>,
     tt: Query<&TileType>,
-    enemies: Query<Entity, With<TargetableEntity>>,
+    enemies: Query<Entity, Or<(With<TargetableEntity>, With<Dying>)>>,
 ) {
     for e in map.storage.iter().flatten() {
         match tt.get(*e).unwrap() {
@@ -239,6 +339,11 @@ fn init(mut cmd: Commands, common: Res<CommonResources>) {
     });
 
     cmd.insert_resource(HealTimer(Timer::from_seconds(10., TimerMode::Repeating)));
+    cmd.insert_resource(Heartbeats::default());
+    cmd.insert_resource(Evaluation::default());
+    cmd.insert_resource(Loops::default());
+    cmd.insert_resource(Credits::default());
+    cmd.insert_resource(WaveDirector::default());
     let layout = HexLayout {
         hex_size: HEX_SIZE,
         ..default()
@@ -284,12 +389,7 @@ fn init(mut cmd: Commands, common: Res<CommonResources>) {
     cmd.insert_resource(Map { layout, storage })
 }
 
-fn setup(
-    mut cmd: Commands,
-    mut mesh: ResMut<Assets<Mesh>>,
-    mut mat: ResMut<Assets<ColorMaterial>>,
-    ass: Res<AssetServer>,
-) {
+fn spawn_camera(mut cmd: Commands) {
     cmd.spawn((
         Camera2dBundle {
             camera: Camera {
@@ -316,7 +416,14 @@ fn setup(
         MainCamera,
         IsDefaultUiCamera,
     ));
+}
 
+fn setup(
+    mut cmd: Commands,
+    mut mesh: ResMut<Assets<Mesh>>,
+    mut mat: ResMut<Assets<ColorMaterial>>,
+    ass: Res<AssetServer>,
+) {
     let layout = HexLayout {
         hex_size: HEX_SIZE,
         ..default()
@@ -363,6 +470,7 @@ fn setup(
         tick_sound: ass.load("sound/tick.ogg"),
         death_sound: ass.load("sound/death.ogg"),
         boot_sound: ass.load("sound/boot.ogg"),
+        font: ass.load("fonts/Geist-Regular.ttf"),
     });
 }
 
@@ -447,141 +555,22 @@ fn heartbeat(
     }
 }
 
-fn request_nodes(mut tick: EventReader<Tick>, mut nodes: Query<&mut CyberState>) {
-    for _ in tick.read() {
-        for mut state in nodes.iter_mut() {
-            if *state != CyberState::Disabled {
-                *state = CyberState::ActivationRequest;
-            }
-        }
-    }
-}
-
-fn tick_nodes(
-    mut cmd: Commands,
-    map: Res<Map>,
-    tiles: Query<&TileType>,
-    nodes: Query<(Entity, &PortCfg, &MetaLink, &HexPos)>,
-    mut states: Query<&mut CyberState>,
-    metas: Query<&CyberNodes>,
-) {
-    for (e, cfg, ml, hex) in nodes.iter() {
-        if *states.get(e).unwrap() != CyberState::ActivationRequest {
-            continue;
-        }
-
-        let node = metas.get(ml.0).unwrap();
-
-        // check if all port nodes are satisfied
-        let inputs: Vec<_> = cfg.inputs.iter().map(|(ph, _)| *ph).collect();
-        let satisfied = inputs.iter().all(|ph| {
-            let Some(tile) = map.storage.get(hex.0 + *ph) else {
-                debug!("not ticking {:?}: input at {:?} oob", node, ph);
-                return false;
-            };
-
-            let TileType::CyberNode { e, .. } = tiles.get(*tile).unwrap() else {
-                debug!(
-                    "not ticking {:?}: input at {:?} with direction {:?} is not CyberNode, is: {:?}",
-                    node,
-                    hex.0 + *ph,
-                    ph,
-                    tiles.get(*tile)
-                );
-                return false;
-            };
-            let Ok(s) = states.get(*e) else {
-                debug!("not ticking {:?}: input at {:?} errored", node, ph);
-                return false;
-            };
-            matches!(*s, CyberState::Done(Ok(..)))
-        });
-
-        let node = *node;
-        if satisfied {
-            cmd.add(move |world: &mut World| {
-                debug!("sending tick evt: {:?}", node);
-                match node {
-                    CyberNodes::WIP => (),
-                    CyberNodes::Lazor => {
-                        world.send_event(TickNode::<Lazor>::new(e));
-                    }
-                    CyberNodes::ClosestEntity => {
-                        world.send_event(TickNode::<ClosestEntity>::new(e));
-                    }
-                    CyberNodes::ConstantNumber => {
-                        world.send_event(TickNode::<ConstantNumber>::new(e));
-                    }
-                    CyberNodes::List => {
-                        world.send_event(TickNode::<List>::new(e));
-                    }
-                    CyberNodes::EntityPos => {
-                        world.send_event(TickNode::<EntityPos>::new(e));
-                    }
-                    CyberNodes::EntityDirection => {
-                        world.send_event(TickNode::<EntityDirection>::new(e));
-                    }
-                    CyberNodes::NearbyEntities => {
-                        world.send_event(TickNode::<NearbyEntity>::new(e));
-                    }
-                    CyberNodes::VectorLen => {
-                        world.send_event(TickNode::<VectorLen>::new(e));
-                    }
-                    CyberNodes::VectorNeg => {
-                        world.send_event(TickNode::<VectorNeg>::new(e));
-                    }
-                    CyberNodes::NumberSub => {
-                        world.send_event(TickNode::<NumberSub>::new(e));
-                    }
-                    CyberNodes::NumberMul => {
-                        world.send_event(TickNode::<NumberMul>::new(e));
-                    }
-                    CyberNodes::VectorMul => {
-                        world.send_event(TickNode::<VectorMul>::new(e));
-                    }
-                    CyberNodes::Project => {
-                        world.send_event(TickNode::<Project>::new(e));
-                    }
-                    CyberNodes::Debug => {
-                        world.send_event(TickNode::<nodes::Debug>::new(e));
-                    }
-                    CyberNodes::Shock => {
-                        world.send_event(TickNode::<Shock>::new(e));
-                    }
-                    CyberNodes::Plasma => {
-                        world.send_event(TickNode::<Plasma>::new(e));
-                    }
-                    CyberNodes::Orbital => {
-                        world.send_event(TickNode::<Orbital>::new(e));
-                    }
-                    CyberNodes::RocketLauncher => {
-                        world.send_event(TickNode::<RocketLauncher>::new(e));
-                    }
-                    CyberNodes::Vector => {
-                        world.send_event(TickNode::<Vector>::new(e));
-                    }
-                    CyberNodes::ListLength => {
-                        world.send_event(TickNode::<ListLen>::new(e));
-                    }
-                    CyberNodes::Storage => {
-                        world.send_event(TickNode::<Storage>::new(e));
-                    }
-                };
-            });
-
-            *states.get_mut(e).unwrap() = CyberState::Triggered;
-        }
-    }
-}
-
 fn tile_purchased(
     mut cmd: Commands,
     mut evt: EventReader<PickedItem>,
     map: ResMut<Map>,
+    mut credits: ResMut<Credits>,
+    prices: Query<&Price>,
     mut mats: Query<&mut Handle<ColorMaterial>>,
     mut tiles: Query<&mut TileType>,
 ) {
     for item in evt.read() {
+        let price = prices.get(item.item).copied().unwrap_or_default();
+        if !credits.spend(price) {
+            warn!("can't afford {:?} for {} credits", price, credits.0);
+            continue;
+        }
+
         let e = map.fetch_panic(item.tile);
         let nodemat = mats
             .get(item.item)
@@ -597,14 +586,11 @@ fn tile_purchased(
         *tt = TileType::CyberNode {
             meta: item.item,
             e: cmd
-                .spawn((
-                    NodeBundle {
-                        meta: MetaLink(item.item),
-                        cfg: PortCfg::default(),
-                        state: CyberState::Disabled,
-                        pos: HexPos(item.tile),
-                    },
-                    Health(10.),
+                .spawn(NodeBundle::placed(
+                    item.item,
+                    PortCfg::default(),
+                    item.tile,
+                    NODE_HP,
                 ))
                 .id(),
         };
@@ -616,25 +602,41 @@ fn remove_node(
     mut click: EventReader<TileClicked>,
     res: Res<CommonResources>,
     map: Res<Map>,
+    mut credits: ResMut<Credits>,
+    prices: Query<&Price>,
     mut tiles: Query<(&mut Handle<ColorMaterial>, &mut TileType)>,
 ) {
     for click in click.read().filter(|c| c.button == MouseButton::Right) {
         let (mut mat, mut tt) = tiles.get_mut(map.fetch_panic(click.tile)).unwrap();
         *mat = res.unoccupied_mat.clone();
-        if let TileType::CyberNode { e, .. } = *tt {
+        if let TileType::CyberNode { e, meta } = *tt {
             cmd.entity(e).despawn();
+            if let Ok(price) = prices.get(meta) {
+                credits.0 += price.refund();
+            }
         }
         *tt = TileType::Unoccupied;
     }
 }
 
+// sent once a frame in which tiles changed their type
 #[derive(Event)]
-struct TileChanged;
+struct TileChanged {
+    tiles: Vec<Hex>,
+}
 
-fn detect_changes(q: Query<Entity, Changed<TileType>>, mut evt: EventWriter<TileChanged>) {
-    if q.iter().next().is_some() {
+fn detect_changes(
+    map: Res<Map>,
+    q: Query<&Transform, Changed<TileType>>,
+    mut evt: EventWriter<TileChanged>,
+) {
+    let tiles: Vec<Hex> = q
+        .iter()
+        .map(|trans| map.layout.world_pos_to_hex(trans.translation.xy()))
+        .collect();
+    if !tiles.is_empty() {
         info!("tile changed, recalculating");
-        evt.send(TileChanged);
+        evt.send(TileChanged { tiles });
     }
 }
 
@@ -679,6 +681,56 @@ fn disable_disconnected(
     }
 }
 
+fn check_wiring(
+    mut cmd: Commands,
+    types: OutputTypes,
+    mut nodes: Query<(Entity, &PortCfg, &HexPos, Option<&mut WiringErrors>)>,
+    ports: Query<&PortMeta>,
+) {
+    for (e, cfg, pos, errors) in nodes.iter_mut() {
+        let mut inputs: Vec<_> = cfg
+            .inputs
+            .iter()
+            .filter_map(|(h, pe)| Some((ports.get(*pe).ok()?, pos.0 + *h)))
+            .collect();
+        inputs.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+
+        let found = WiringErrors(
+            inputs
+                .into_iter()
+                .filter_map(|(port, at)| types.check(port, at).err())
+                .collect(),
+        );
+
+        match errors {
+            Some(mut errors) => {
+                errors.set_if_neq(found);
+            }
+            None => {
+                cmd.entity(e).insert(found);
+            }
+        }
+    }
+}
+
+// wrongly wired nodes get a red ring
+// nodes that can't be wired like this, or that are wired in a loop
+fn draw_wiring_errors(
+    mut gizmos: Gizmos,
+    map: Res<Map>,
+    loops: Res<Loops>,
+    nodes: Query<(&HexPos, &WiringErrors)>,
+) {
+    let wrong = nodes
+        .iter()
+        .filter(|(_, errors)| !errors.0.is_empty())
+        .map(|(pos, _)| pos.0);
+    for tile in wrong.chain(loops.0.iter().copied()) {
+        let center = map.layout.hex_to_world_pos(tile);
+        gizmos.circle_2d(center, HEX_SIZE.x * 0.8, Color::from(RED));
+    }
+}
+
 fn click(
     selection: Res<Selection>,
     input: Res<ButtonInput<MouseButton>>,
@@ -737,6 +789,13 @@ fn open_death(
     }
 }
 
+// whether any tile next to `tile` is connected, which is checked on the tile entity
+fn adjacent(map: &Map, tile: Hex, connected: impl Fn(Entity) -> bool) -> bool {
+    tile.all_neighbors()
+        .iter()
+        .any(|h| map.storage.get(*h).is_some_and(|e| connected(*e)))
+}
+
 fn open_shop(
     map: Res<Map>,
     typeq: Query<&TileType>,
@@ -749,22 +808,12 @@ fn open_shop(
         if evt.button == MouseButton::Left
             && *typeq.get(map.fetch_panic(evt.tile)).unwrap() == TileType::Unoccupied
         {
-            // check if adjacent to other tile
-            let adjacent = evt.tile.all_neighbors().iter().any(|h| {
-                let Some(e) = map.storage.get(*h) else {
-                    return false;
-                };
-
-                match typeq.get(*e).unwrap() {
-                    TileType::CyberNode { e, .. } => {
-                        *stateq.get(*e).unwrap() != CyberState::Disabled
-                    }
-                    TileType::Heart(_) => true,
-                    _ => false,
-                }
-            });
-
-            if !adjacent {
+            let connected = |e| match typeq.get(e).unwrap() {
+                TileType::CyberNode { e, .. } => *stateq.get(*e).unwrap() != CyberState::Disabled,
+                TileType::Heart(_) => true,
+                _ => false,
+            };
+            if !adjacent(&map, evt.tile, connected) {
                 continue;
             };
 
@@ -1,18 +1,32 @@
-use std::{collections::HashMap, f32::consts::PI, fmt, marker::PhantomData};
+use std::{collections::HashMap, f32::consts::PI, fmt};
 
 use bevy::{
-    ecs::system::QueryLens,
+    ecs::system::{
+        lifetimeless::{SCommands, SQuery, SRes, SResMut},
+        SystemParam, SystemParamItem,
+    },
     gizmos::gizmos,
     math::{
         bounding::{BoundingCircle, IntersectsVolume},
-        quat, vec2, vec3, NormedVectorSpace, VectorSpace,
+        quat, vec2, vec3,
     },
     prelude::*,
     sprite::MaterialMesh2dBundle,
 };
-use hexx::{EdgeDirection, Hex};
-
-use crate::{enemy::PathfindPath, CommonResources, Gamestate, Map, TileType};
+use hexx::{EdgeDirection, Hex, HexLayout};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    economy::Price,
+    enemy::{self, Archetype, Archetypes, EnemyActivity, PathfindPath, Speed},
+    registry::{
+        apply_cyber_node, CyberNode, ExclusiveCyberNode, NodeCtx, NodeInputs, NodeRegistry,
+        RegisterCyberNode,
+    },
+    scheduler::{evaluate_heartbeats, Heartbeats},
+    CommonResources, Gamestate, Heartbeat, Map, SimRng, SimSet, TileType,
+};
 
 #[derive(Component, Clone)]
 pub struct Name(pub String);
@@ -20,6 +34,10 @@ pub struct Name(pub String);
 #[derive(Component, Clone)]
 pub struct Description(pub String);
 
+// short text drawn over the texture of an item, set by the catalog
+#[derive(Component, Clone)]
+pub struct IconLabel(pub String);
+
 #[derive(Component, Clone)]
 pub struct ItemMeta;
 
@@ -41,7 +59,7 @@ pub struct PortCfg {
 #[derive(Component, Clone)]
 pub struct PortMetas(pub Vec<Entity>);
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
 pub enum ValType {
     Empty,
     Any,
@@ -50,6 +68,7 @@ pub enum ValType {
     Number,
     Text,
     List,
+    Bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -60,6 +79,7 @@ pub enum Val {
     Number(f32),
     Text(String),
     List(Vec<Val>),
+    Bool(bool),
 }
 
 #[derive(Component)]
@@ -68,19 +88,132 @@ pub struct TargetableEntity;
 #[derive(Component)]
 pub struct Health(pub f32);
 
+// what kind of node dealt damage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum DamageKind {
+    Beam,
+    Explosive,
+    Plasma,
+    Electric,
+}
+
+// the damage kinds an entity ignores
+#[derive(Component)]
+pub struct Shield(pub Vec<DamageKind>);
+
+impl Health {
+    pub fn damage(&mut self, dmg: f32, kind: DamageKind, shield: Option<&Shield>) {
+        if shield.is_some_and(|s| s.0.contains(&kind)) {
+            return;
+        }
+        self.0 -= dmg;
+    }
+}
+
 #[derive(Component)]
 pub struct MetaLink(pub Entity);
 
-#[derive(Component, Default, PartialEq)]
+#[derive(Component, Default, PartialEq, Clone, Debug)]
 pub enum CyberState {
     #[default]
     Idle,
-    ActivationRequest,
-    Triggered,
-    Done(Result<Val, ()>),
+    Done(Result<Val, NodeError>),
+    // chose not to run this heartbeat, see `NodeError::Skipped`
+    Skipped,
     Disabled,
 }
 
+// why a node failed to evaluate
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeError {
+    // the port is not wired up
+    NotConfigured(String),
+    // the port is wired to a tile without a node
+    NoInput {
+        port: String,
+        at: Hex,
+    },
+    // the node wired to the port failed, `at` is where the failure started
+    Upstream {
+        port: String,
+        at: Hex,
+    },
+    WrongType {
+        port: String,
+        expected: ValType,
+        got: ValType,
+    },
+    OutOfRange {
+        port: String,
+        value: f32,
+    },
+    // the text on the port doesn't name anything the node knows
+    UnknownName {
+        port: String,
+        name: String,
+    },
+    NoConstant,
+    // no entity in range
+    NoTarget,
+    // the target entity no longer exists
+    TargetGone,
+    OutOfBounds(Hex),
+    Occupied(Hex),
+    // the node reads from itself, through other nodes
+    Cycle,
+    // not a failure: the node or a required input did not run this heartbeat.
+    // nodes ending with it are `CyberState::Skipped`, optional inputs ignore it
+    Skipped,
+    // the node could not be run at all
+    Internal,
+}
+
+impl NodeError {
+    // the error a node gets when the node at `at` wired to `port` failed with `self`
+    pub fn upstream(&self, port: &str, at: Hex) -> Self {
+        let at = match self {
+            Self::Upstream { at, .. } => *at,
+            Self::Skipped => return Self::Skipped,
+            _ => at,
+        };
+        Self::Upstream {
+            port: port.to_string(),
+            at,
+        }
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConfigured(port) => write!(f, "port {} is not configured", port),
+            Self::NoInput { port, at } => {
+                write!(f, "port {}: no node at ({}, {})", port, at.x, at.y)
+            }
+            Self::Upstream { port, at } => {
+                write!(f, "port {}: upstream failed at ({}, {})", port, at.x, at.y)
+            }
+            Self::WrongType {
+                port,
+                expected,
+                got,
+            } => write!(f, "port {}: expected {}, got {}", port, expected, got),
+            Self::OutOfRange { port, value } => {
+                write!(f, "port {}: {} is out of range", port, value)
+            }
+            Self::UnknownName { port, name } => write!(f, "port {}: unknown name {:?}", port, name),
+            Self::NoConstant => write!(f, "no constant configured"),
+            Self::NoTarget => write!(f, "no entity in range"),
+            Self::TargetGone => write!(f, "target no longer exists"),
+            Self::OutOfBounds(at) => write!(f, "({}, {}) is out of bounds", at.x, at.y),
+            Self::Occupied(at) => write!(f, "({}, {}) is already occupied", at.x, at.y),
+            Self::Cycle => write!(f, "wired in a loop, store a value to feed it back"),
+            Self::Skipped => write!(f, "skipped"),
+            Self::Internal => write!(f, "node could not be evaluated"),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct HexPos(pub Hex);
 
@@ -92,6 +225,24 @@ pub struct NodeBundle {
     pub pos: HexPos,
 }
 
+// health of a freshly bought node
+pub const NODE_HP: f32 = 10.;
+
+impl NodeBundle {
+    // every node is placed disabled, until it's found connected to the heart
+    pub fn placed(meta: Entity, cfg: PortCfg, tile: Hex, hp: f32) -> (Self, Health) {
+        (
+            Self {
+                meta: MetaLink(meta),
+                cfg,
+                state: CyberState::Disabled,
+                pos: HexPos(tile),
+            },
+            Health(hp),
+        )
+    }
+}
+
 #[derive(Component, Clone, Copy)]
 pub struct OutputPort(pub Entity);
 
@@ -103,7 +254,8 @@ pub struct ItemMetaBundle {
     pub mat: Handle<ColorMaterial>,
     pub ports: PortMetas,
     pub output: OutputPort,
-    pub node: CyberNodes,
+    pub node: NodeKind,
+    pub price: Price,
     meta: ItemMeta,
 }
 
@@ -113,6 +265,130 @@ impl fmt::Display for ValType {
     }
 }
 
+impl Val {
+    pub fn vt(&self) -> ValType {
+        match self {
+            Val::Empty => ValType::Empty,
+            Val::Entity(_) => ValType::Entity,
+            Val::Vec(_) => ValType::Vec,
+            Val::Number(_) => ValType::Number,
+            Val::Text(_) => ValType::Text,
+            Val::List(_) => ValType::List,
+            Val::Bool(_) => ValType::Bool,
+        }
+    }
+}
+
+// how values are shown to the player
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Val::Empty => Ok(()),
+            Val::Entity(e) => write!(f, "entity {}", e.index()),
+            Val::Vec(v) => write!(f, "({}, {})", v.x, v.y),
+            Val::Number(n) => write!(f, "{}", n),
+            Val::Text(t) => write!(f, "{}", t),
+            Val::List(l) => {
+                write!(f, "[")?;
+                for (i, v) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Val::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl ValType {
+    // whether an input of this type can be wired to an output of type `output`.
+    // `Any` outputs are only known at tick time so they are always accepted
+    pub fn accepts(self, output: ValType) -> bool {
+        self == ValType::Any || output == ValType::Any || self == output
+    }
+}
+
+// an input port that is wired up wrong
+#[derive(Clone, Debug, PartialEq)]
+pub enum WiringError {
+    // the wired tile has no node on it
+    NoNode {
+        port: String,
+        at: Hex,
+    },
+    Mismatch {
+        port: String,
+        expected: ValType,
+        got: ValType,
+    },
+}
+
+impl WiringError {
+    pub fn port(&self) -> &str {
+        match self {
+            Self::NoNode { port, .. } | Self::Mismatch { port, .. } => port,
+        }
+    }
+}
+
+impl fmt::Display for WiringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoNode { port, at } => {
+                write!(f, "{}: no node at ({}, {})", port, at.x, at.y)
+            }
+            Self::Mismatch {
+                port,
+                expected,
+                got,
+            } => write!(f, "{}: expected {}, got {}", port, expected, got),
+        }
+    }
+}
+
+// kept up to date with the wiring of a placed node
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+pub struct WiringErrors(pub Vec<WiringError>);
+
+// output types of the nodes placed on the map
+#[derive(SystemParam)]
+pub struct OutputTypes<'w, 's> {
+    map: Res<'w, Map>,
+    tiles: Query<'w, 's, &'static TileType>,
+    outputs: Query<'w, 's, &'static OutputPort>,
+    metas: Query<'w, 's, &'static PortMeta>,
+}
+
+impl OutputTypes<'_, '_> {
+    // `None` if there is no node on the tile
+    pub fn at(&self, h: Hex) -> Option<ValType> {
+        let tile = self.map.storage.get(h)?;
+        let TileType::CyberNode { meta, .. } = self.tiles.get(*tile).ok()? else {
+            return None;
+        };
+        let output = self.outputs.get(*meta).ok()?;
+        Some(self.metas.get(output.0).ok()?.vt)
+    }
+
+    pub fn check(&self, port: &PortMeta, at: Hex) -> Result<(), WiringError> {
+        match self.at(at) {
+            None => Err(WiringError::NoNode {
+                port: port.name.clone(),
+                at,
+            }),
+            Some(vt) if !port.vt.accepts(vt) => Err(WiringError::Mismatch {
+                port: port.name.clone(),
+                expected: port.vt,
+                got: vt,
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
 impl PortMeta {
     pub fn new_meta(name: String, desc: String, t: ValType, constant: bool) -> Self {
         Self {
@@ -131,7 +407,8 @@ impl ItemMetaBundle {
         ports: &[Entity],
         outputs: Entity,
         tex: Handle<Image>,
-        node: CyberNodes,
+        node: NodeKind,
+        price: Price,
         mats: &mut Assets<ColorMaterial>,
     ) -> Self {
         Self {
@@ -146,50 +423,15 @@ impl ItemMetaBundle {
             output: OutputPort(outputs),
             meta: ItemMeta,
             node,
+            price,
         }
     }
 }
 
-#[derive(Component, Debug, Clone, Copy)]
-pub enum CyberNodes {
-    WIP,
-    Lazor,
-    List,
-    Debug,
-    Project,
-    Shock,
-    Plasma,
-    Orbital,
-    RocketLauncher,
-    ClosestEntity,
-    EntityDirection,
-    NearbyEntities,
-    EntityPos,
-    ConstantNumber,
-    NumberSub,
-    Storage,
-    NumberMul,
-    VectorMul,
-    VectorNeg,
-    ListLength,
-    VectorLen,
-    Vector,
-}
-
-#[derive(Event)]
-pub struct TickNode<T> {
-    pub e: Entity,
-    _pd: PhantomData<T>,
-}
-
-impl<T> TickNode<T> {
-    pub fn new(e: Entity) -> Self {
-        Self {
-            e,
-            _pd: PhantomData::default(),
-        }
-    }
-}
+// id of the registered CyberNode an item places
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeKind(pub String);
 
 #[derive(Default)]
 pub struct Lazor;
@@ -233,32 +475,166 @@ pub struct VectorNeg;
 pub struct NumberMul;
 #[derive(Default)]
 pub struct NumberSub;
+#[derive(Default)]
+pub struct NumberAdd;
+#[derive(Default)]
+pub struct NumberDiv;
+#[derive(Default)]
+pub struct NumberMin;
+#[derive(Default)]
+pub struct NumberMax;
+#[derive(Default)]
+pub struct NumberAbs;
+#[derive(Default)]
+pub struct VectorAdd;
+#[derive(Default)]
+pub struct VectorSub;
+#[derive(Default)]
+pub struct VectorDot;
+#[derive(Default)]
+pub struct VectorNormalize;
+#[derive(Default)]
+pub struct VectorRotate;
+#[derive(Default)]
+pub struct ListSum;
+#[derive(Default)]
+pub struct ConstantBool;
+#[derive(Default)]
+pub struct NumberLess;
+#[derive(Default)]
+pub struct NumberGreater;
+#[derive(Default)]
+pub struct NumberEqual;
+#[derive(Default)]
+pub struct BoolAnd;
+#[derive(Default)]
+pub struct BoolOr;
+#[derive(Default)]
+pub struct BoolXor;
+#[derive(Default)]
+pub struct BoolNot;
+#[derive(Default)]
+pub struct Select;
+#[derive(Default)]
+pub struct ListIndex;
+#[derive(Default)]
+pub struct ListFirst;
+#[derive(Default)]
+pub struct ListLast;
+#[derive(Default)]
+pub struct ListSortDistance;
+#[derive(Default)]
+pub struct ListSortHealth;
+#[derive(Default)]
+pub struct ListFilterHealth;
+#[derive(Default)]
+pub struct ListFilterArchetype;
+#[derive(Default)]
+pub struct ListPositions;
+#[derive(Default)]
+pub struct ListMap;
+#[derive(Default)]
+pub struct EntityHealth;
+#[derive(Default)]
+pub struct EntityArchetype;
+#[derive(Default)]
+pub struct EntityDistance;
+#[derive(Default)]
+pub struct EntitySpeed;
+#[derive(Default)]
+pub struct EntityPredict;
+#[derive(Default)]
+pub struct HeartHealth;
+#[derive(Default)]
+pub struct HeartRate;
+#[derive(Default)]
+pub struct TickCounter;
+#[derive(Default)]
+pub struct SelfPosition;
+#[derive(Default)]
+pub struct EnemyCount;
+#[derive(Default)]
+pub struct Random;
+#[derive(Default)]
+pub struct ConstantText;
+#[derive(Default)]
+pub struct TextConcat;
+#[derive(Default)]
+pub struct TextFormat;
+#[derive(Default)]
+pub struct TextDisplay;
 
 pub struct CyberPlugin;
 impl Plugin for CyberPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<TickNode<Lazor>>()
-            .add_event::<TickNode<RocketLauncher>>()
-            .add_event::<TickNode<Orbital>>()
-            .add_event::<TickNode<Shock>>()
-            .add_event::<TickNode<Project>>()
-            .add_event::<TickNode<Plasma>>()
-            .add_event::<TickNode<Debug>>()
-            .add_event::<TickNode<ListLen>>()
-            .add_event::<TickNode<ClosestEntity>>()
-            .add_event::<TickNode<NearbyEntity>>()
-            .add_event::<TickNode<EntityDirection>>()
-            .add_event::<TickNode<EntityPos>>()
-            .add_event::<TickNode<ConstantNumber>>()
-            .add_event::<TickNode<List>>()
-            .add_event::<TickNode<VectorMul>>()
-            .add_event::<TickNode<VectorLen>>()
-            .add_event::<TickNode<VectorNeg>>()
-            .add_event::<TickNode<Vector>>()
-            .add_event::<TickNode<NumberMul>>()
-            .add_event::<TickNode<NumberSub>>()
+        app.register_cyber_node::<Lazor>()
+            .register_cyber_node::<RocketLauncher>()
+            .register_cyber_node::<Orbital>()
+            .register_cyber_node::<Shock>()
+            .register_cyber_node::<Project>()
+            .register_cyber_node::<Plasma>()
+            .register_cyber_node::<Debug>()
+            .register_cyber_node::<ListLen>()
+            .register_cyber_node::<ClosestEntity>()
+            .register_cyber_node::<NearbyEntity>()
+            .register_cyber_node::<EntityDirection>()
+            .register_cyber_node::<EntityPos>()
+            .register_cyber_node::<ConstantNumber>()
+            .register_cyber_node::<List>()
+            .register_cyber_node::<VectorMul>()
+            .register_cyber_node::<VectorLen>()
+            .register_cyber_node::<VectorNeg>()
+            .register_cyber_node::<Vector>()
+            .register_cyber_node::<NumberMul>()
+            .register_cyber_node::<NumberSub>()
+            .register_cyber_node::<NumberAdd>()
+            .register_cyber_node::<NumberDiv>()
+            .register_cyber_node::<NumberMin>()
+            .register_cyber_node::<NumberMax>()
+            .register_cyber_node::<NumberAbs>()
+            .register_cyber_node::<VectorAdd>()
+            .register_cyber_node::<VectorSub>()
+            .register_cyber_node::<VectorDot>()
+            .register_cyber_node::<VectorNormalize>()
+            .register_cyber_node::<VectorRotate>()
+            .register_cyber_node::<ListSum>()
+            .register_cyber_node::<ConstantBool>()
+            .register_cyber_node::<NumberLess>()
+            .register_cyber_node::<NumberGreater>()
+            .register_cyber_node::<NumberEqual>()
+            .register_cyber_node::<BoolAnd>()
+            .register_cyber_node::<BoolOr>()
+            .register_cyber_node::<BoolXor>()
+            .register_cyber_node::<BoolNot>()
+            .register_cyber_node::<Select>()
+            .register_cyber_node::<ListIndex>()
+            .register_cyber_node::<ListFirst>()
+            .register_cyber_node::<ListLast>()
+            .register_cyber_node::<ListSortDistance>()
+            .register_cyber_node::<ListSortHealth>()
+            .register_cyber_node::<ListFilterHealth>()
+            .register_cyber_node::<ListFilterArchetype>()
+            .register_cyber_node::<ListPositions>()
+            .register_exclusive_cyber_node::<ListMap>()
+            .register_cyber_node::<EntityHealth>()
+            .register_cyber_node::<EntityArchetype>()
+            .register_cyber_node::<EntityDistance>()
+            .register_cyber_node::<EntitySpeed>()
+            .register_cyber_node::<EntityPredict>()
+            .register_cyber_node::<HeartHealth>()
+            .register_cyber_node::<HeartRate>()
+            .register_cyber_node::<TickCounter>()
+            .register_cyber_node::<SelfPosition>()
+            .register_cyber_node::<EnemyCount>()
+            .register_cyber_node::<Random>()
+            .register_cyber_node::<ConstantText>()
+            .register_cyber_node::<TextConcat>()
+            .register_cyber_node::<TextFormat>()
+            .register_cyber_node::<TextDisplay>()
+            .register_cyber_node::<Storage>()
+            // projectiles deal damage, so they stop with the rest of the simulation
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
                     rocket_anim,
                     lazorbeam_anim,
@@ -267,89 +643,40 @@ impl Plugin for CyberPlugin {
                     orbital_target,
                     tesla_anim,
                 )
-                    .run_if(in_state(Gamestate::Game)),
+                    .chain()
+                    .in_set(SimSet::Nodes)
+                    .after(evaluate_heartbeats),
             )
             .add_systems(
-                FixedUpdate,
-                (
-                    lazor_tick,
-                    closest_tick,
-                    constant_tick,
-                    list_tick,
-                    listlen_tick,
-                    entity_pos_tick,
-                    entity_dir_tick,
-                    rocket_launcher_tick,
-                    orbital_tick,
-                    tesla_tick,
-                    project_tick,
-                    plasma_tick,
-                    vecmul_tick,
-                    nummul_tick,
-                    numsub_tick,
-                    debug_tick,
-                    vec_tick,
-                    veclen_tick,
-                    vecneg_tick,
-                    nearby_tick,
-                )
-                    .run_if(in_state(Gamestate::Game)),
+                Update,
+                remove_display_labels.run_if(in_state(Gamestate::Game)),
             );
     }
 }
 
-type TickEvts<'a, 'b, T> = EventReader<'a, 'b, TickNode<T>>;
-
-fn port_by_name(
-    name: &str,
-    pos: Hex,
-    map: &Map,
-    cfg: &PortCfg,
-    meta: &mut QueryLens<&PortMeta>,
-    tiles: &mut QueryLens<&TileType>,
-) -> Option<Entity> {
-    let meta = meta.query();
-    let tiles = tiles.query();
-    cfg.inputs
-        .iter()
-        .find_map(|(h, e)| (meta.get(*e).unwrap().name == name).then_some(h))
-        .map(|h| match tiles.get(map.fetch_panic(pos + *h)).unwrap() {
-            TileType::CyberNode { e, .. } => e,
-            _ => unreachable!(),
-        })
-        .copied()
-}
-
-fn fetch_port_data(
-    name: &str,
-    pos: Hex,
-    map: &Map,
-    cfg: &PortCfg,
-    metas: &mut QueryLens<&PortMeta>,
-    states: &mut QueryLens<&CyberState>,
-    tiles: &mut QueryLens<&TileType>,
-) -> Result<Val, ()> {
-    let states = states.query();
-
-    let Some(target) = port_by_name(name, pos, &map, cfg, metas, tiles) else {
-        error!("{:?} port not configured", name);
-        return Err(());
-    };
+// ports of nodes taking an arbitrary amount of inputs.
+// they fold the connected ports in order and skip values of other types
+const VARIADIC: &[&str] = &["a", "b", "c", "d", "e"];
 
-    let Ok(target_state) = states.get(target) else {
-        warn!("port {:?} tile no longer exists", name);
-        return Err(());
-    };
+fn numbers(inputs: &NodeInputs) -> impl Iterator<Item = f32> + '_ {
+    inputs.values(VARIADIC).filter_map(|v| match v {
+        Val::Number(x) => Some(*x),
+        _ => None,
+    })
+}
 
-    let CyberState::Done(Ok(v)) = target_state else {
-        warn!(
-            "port {:?} tile changed state unexpectedly, or has invalid type",
-            name
-        );
-        return Err(());
-    };
+fn bools(inputs: &NodeInputs) -> impl Iterator<Item = bool> + '_ {
+    inputs.values(VARIADIC).filter_map(|v| match v {
+        Val::Bool(x) => Some(*x),
+        _ => None,
+    })
+}
 
-    return Ok(v.clone());
+fn vectors(inputs: &NodeInputs) -> impl Iterator<Item = Vec2> + '_ {
+    inputs.values(VARIADIC).filter_map(|v| match v {
+        Val::Vec(x) => Some(*x),
+        _ => None,
+    })
 }
 
 #[derive(Component)]
@@ -365,53 +692,29 @@ struct LazorbeamBundle {
     marker: Lazorbeam,
 }
 
-fn lazor_tick(
-    mut evt: TickEvts<Lazor>,
-    mut cmd: Commands,
-    res: Res<CommonResources>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut state: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-    mut targets: Query<&mut Health, With<TargetableEntity>>,
-) {
-    const DMG: f32 = 15.;
-    for e in evt.read() {
+impl CyberNode for Lazor {
+    const ID: &'static str = "lazor";
+    const INPUTS: &'static [&'static str] = &["target", "enable"];
+    type Param = (
+        SCommands,
+        SRes<CommonResources>,
+        SRes<Map>,
+        SQuery<(&'static mut Health, Option<&'static Shield>), With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, res, map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        const DMG: f32 = 15.;
         info!("ticking lazor");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
-        };
-
-        let Some(target) = port_by_name(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("lazor: 'target' port not configured");
-            let mut state = state.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            return;
-        };
-        let Ok(target_state) = state.get(target) else {
-            warn!("lazor: port tile no longer exists");
-            continue;
-        };
+        let target = inputs.entity("target")?;
 
-        let CyberState::Done(Ok(Val::Entity(target))) = *target_state else {
-            warn!("lazor: port tile changed state unexpectedly, or has invalid type");
-            continue;
-        };
-
-        let mut state = state.get_mut(e.e).unwrap();
-        let Ok(mut hp) = targets.get_mut(target) else {
+        let Ok((mut hp, shield)) = targets.get_mut(target) else {
             // entity no longer exists
             warn!("lazor: tried to lazor entity that no longer exists or has no health");
-            *state = CyberState::Done(Err(()));
-            continue;
+            return Err(NodeError::TargetGone);
         };
 
         cmd.spawn(LazorbeamBundle {
@@ -419,7 +722,7 @@ fn lazor_tick(
             apperance: MaterialMesh2dBundle {
                 visibility: Visibility::Hidden,
                 transform: Transform::from_translation(
-                    map.layout.hex_to_world_pos(pos.0).extend(2.),
+                    map.layout.hex_to_world_pos(node.pos).extend(2.),
                 ),
                 mesh: res.lazer.clone(),
                 material: res.lazer_mat.clone(),
@@ -428,8 +731,8 @@ fn lazor_tick(
             marker: Lazorbeam,
         });
 
-        hp.0 -= DMG;
-        *state = CyberState::Done(Ok(Val::Empty));
+        hp.damage(DMG, DamageKind::Beam, shield);
+        Ok(Val::Empty)
     }
 }
 
@@ -470,15 +773,15 @@ fn rocket_anim(
         (Entity, &Direction, &mut Transform, &mut Visibility),
         (With<Rocket>, Without<TargetableEntity>),
     >,
-    mut targets: Query<(&Transform, &mut Health), With<TargetableEntity>>,
+    mut targets: Query<(&Transform, &mut Health, Option<&Shield>), With<TargetableEntity>>,
 ) {
     for (ent, dir, mut trans, mut vis) in rockets.iter_mut() {
         let bb = BoundingCircle::new(trans.translation.xy(), 10.);
         let mut hit = false;
-        for (tt, mut hp) in targets.iter_mut() {
+        for (tt, mut hp, shield) in targets.iter_mut() {
             if bb.intersects(&BoundingCircle::new(tt.translation.xy(), 7.)) {
                 hit = true;
-                hp.0 -= 50.;
+                hp.damage(50., DamageKind::Explosive, shield);
             }
         }
 
@@ -506,6 +809,9 @@ struct RocketBundle {
     marker: Rocket,
 }
 
+// seconds until a requested orbital strike hits
+const ORBITAL_DELAY: f32 = 10.;
+
 #[derive(Component)]
 struct OrbitalTimer(Timer);
 
@@ -520,50 +826,24 @@ struct OrbitalBundle {
     marker: OrbitalMarker,
 }
 
-fn rocket_launcher_tick(
-    mut cmd: Commands,
-    mut evt: TickEvts<RocketLauncher>,
-    res: Res<CommonResources>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut state: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking rocket launcher");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
-        };
-
-        let Some(dir) = port_by_name(
-            "direction",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("launcher: 'direction' port not configured");
-            let mut state = state.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            return;
-        };
-        let Ok(target_state) = state.get(dir) else {
-            warn!("launcher: port tile no longer exists");
-            continue;
-        };
+impl CyberNode for RocketLauncher {
+    const ID: &'static str = "rocket_launcher";
+    const INPUTS: &'static [&'static str] = &["direction", "enable"];
+    type Param = (SCommands, SRes<CommonResources>, SRes<Map>);
 
-        let CyberState::Done(Ok(Val::Vec(dir))) = *target_state else {
-            warn!("launcher: 'direction' port error or has invalid type");
-            continue;
-        };
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, res, map): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking rocket launcher");
+        let dir = inputs.vec("direction")?;
 
         cmd.spawn(RocketBundle {
             dir: Direction(dir.normalize()),
             apperance: MaterialMesh2dBundle {
                 transform: Transform::from_translation(
-                    map.layout.hex_to_world_pos(pos.0).extend(2.),
+                    map.layout.hex_to_world_pos(node.pos).extend(2.),
                 ),
                 mesh: res.missile.clone(),
                 material: res.missile_mat.clone(),
@@ -571,42 +851,26 @@ fn rocket_launcher_tick(
             },
             marker: Rocket,
         });
-        let mut state = state.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Empty));
+        Ok(Val::Empty)
     }
 }
 
-fn orbital_tick(
-    mut cmd: Commands,
-    mut evt: TickEvts<Orbital>,
-    common: Res<CommonResources>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking orbital strike");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            continue;
-        };
+impl CyberNode for Orbital {
+    const ID: &'static str = "orbital";
+    const INPUTS: &'static [&'static str] = &["target", "enable"];
+    type Param = (SCommands, SRes<CommonResources>, SRes<Map>);
 
-        let Ok(Val::Vec(pos)) = fetch_port_data(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            continue;
-        };
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, common, map): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking orbital strike");
+        let pos = inputs.vec("target")?;
 
         cmd.spawn(OrbitalBundle {
             marker: OrbitalMarker,
-            timer: OrbitalTimer(Timer::from_seconds(10., TimerMode::Once)),
+            timer: OrbitalTimer(Timer::from_seconds(ORBITAL_DELAY, TimerMode::Once)),
             target: HexPos(Hex::round(pos.to_array())),
             apperance: SpriteBundle {
                 transform: Transform::from_translation(
@@ -618,8 +882,7 @@ fn orbital_tick(
             },
         });
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Empty));
+        Ok(Val::Empty)
     }
 }
 
@@ -639,7 +902,7 @@ fn orbital_target(
     common: Res<CommonResources>,
     mut mats: ResMut<Assets<ColorMaterial>>,
     mut timers: Query<(Entity, &Transform, &mut OrbitalTimer)>,
-    mut targets: Query<(&Transform, &mut Health), With<TargetableEntity>>,
+    mut targets: Query<(&Transform, &mut Health, Option<&Shield>), With<TargetableEntity>>,
 ) {
     for (e, trans, mut timer) in timers.iter_mut() {
         if !timer.0.tick(time.delta()).just_finished() {
@@ -647,9 +910,9 @@ fn orbital_target(
         }
 
         let bb = BoundingCircle::new(trans.translation.xy(), 200.);
-        for (tt, mut hp) in targets.iter_mut() {
+        for (tt, mut hp, shield) in targets.iter_mut() {
             if bb.intersects(&BoundingCircle::new(tt.translation.xy(), 5.)) {
-                hp.0 -= 5.;
+                hp.damage(5., DamageKind::Explosive, shield);
             }
         }
 
@@ -694,7 +957,7 @@ fn explosion_anim(
 }
 
 #[derive(Component)]
-struct PlasmaCounter(u32);
+pub struct PlasmaCounter(pub u32);
 
 #[derive(Component)]
 struct PlasmaMarker;
@@ -714,50 +977,31 @@ struct PlasmaBundle {
     marker: PlasmaMarker,
 }
 
-fn plasma_tick(
-    mut cmd: Commands,
-    mut evt: TickEvts<Plasma>,
-    common: Res<CommonResources>,
-    mut mats: ResMut<Assets<ColorMaterial>>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut counters: Query<&mut PlasmaCounter>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
+impl CyberNode for Plasma {
+    const ID: &'static str = "plasma";
+    const INPUTS: &'static [&'static str] = &["target", "threshold", "enable"];
+    type Param = (
+        SCommands,
+        SRes<CommonResources>,
+        SResMut<Assets<ColorMaterial>>,
+        SRes<Map>,
+        SQuery<&'static mut PlasmaCounter>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, common, mats, map, counters): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking plasma cannon");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            continue;
-        };
-
-        let Ok(Val::Vec(target)) = fetch_port_data(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            continue;
-        };
+        let target = inputs.vec("target")?;
 
-        let threshold = match fetch_port_data(
-            "threshold",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) {
-            Ok(Val::Number(t)) => t,
+        let threshold = match inputs.optional("threshold") {
+            Some(Ok(Val::Number(t))) => *t,
             _ => 0.,
         };
 
-        let mut count = counters.get(e.e).map(|c| c.0).unwrap_or(0) + 1;
+        let mut count = counters.get(node.e).map(|c| c.0).unwrap_or(0) + 1;
         let mut res = 0;
         if count as f32 >= threshold {
             let color = vec3(0.541, 0.168, 0.886) * count as f32;
@@ -781,14 +1025,13 @@ fn plasma_tick(
             count = 0;
         }
 
-        if let Ok(mut counter) = counters.get_mut(e.e) {
+        if let Ok(mut counter) = counters.get_mut(node.e) {
             counter.0 = count;
         } else {
-            cmd.entity(e.e).insert(PlasmaCounter(count));
+            cmd.entity(node.e).insert(PlasmaCounter(count));
         }
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(res as f32)));
+        Ok(Val::Number(res as f32))
     }
 }
 
@@ -806,7 +1049,7 @@ fn plasma_anim(
         ),
         (With<PlasmaMarker>, Without<TargetableEntity>),
     >,
-    mut targets: Query<(&Transform, &mut Health), With<TargetableEntity>>,
+    mut targets: Query<(&Transform, &mut Health, Option<&Shield>), With<TargetableEntity>>,
 ) {
     for (e, mut timer, mut dmgtimer, trans, mat) in plasmas.iter_mut() {
         if timer.0.tick(time.delta()).finished() {
@@ -820,9 +1063,9 @@ fn plasma_anim(
         let dmg = timer.0.remaining_secs() * 5.;
         if dmgtimer.0.tick(time.delta()).just_finished() {
             let bb = BoundingCircle::new(trans.translation.xy(), 100.);
-            for (tt, mut hp) in targets.iter_mut() {
+            for (tt, mut hp, shield) in targets.iter_mut() {
                 if bb.intersects(&BoundingCircle::new(tt.translation.xy(), 5.)) {
-                    hp.0 -= dmg;
+                    hp.damage(dmg, DamageKind::Plasma, shield);
                 }
             }
         }
@@ -838,34 +1081,23 @@ struct TeslaTargets(Vec<Entity>);
 #[derive(Component)]
 struct TeslaOrig(Vec2);
 
-fn tesla_tick(
-    mut evt: TickEvts<Shock>,
-    mut cmd: Commands,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-    mut targetable: Query<&mut Health, With<TargetableEntity>>,
-) {
-    const DMG: f32 = 5.;
-    for e in evt.read() {
+impl CyberNode for Shock {
+    const ID: &'static str = "shock";
+    const INPUTS: &'static [&'static str] = &["targets", "enable"];
+    type Param = (
+        SCommands,
+        SRes<Map>,
+        SQuery<(&'static mut Health, Option<&'static Shield>), With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, map, targetable): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        const DMG: f32 = 5.;
         info!("ticking tesla coil");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            continue;
-        };
-
-        let Ok(Val::List(targets)) = fetch_port_data(
-            "targets",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            continue;
-        };
+        let targets = inputs.list("targets")?;
 
         let mut res = 0;
         let mut ents = Vec::new();
@@ -875,23 +1107,22 @@ fn tesla_tick(
                 continue;
             };
 
-            let Ok(mut hp) = targetable.get_mut(e) else {
+            let Ok((mut hp, shield)) = targetable.get_mut(*e) else {
                 warn!("tesla coil: target no longer exists");
                 continue;
             };
 
-            ents.push(e);
-            hp.0 -= DMG;
+            ents.push(*e);
+            hp.damage(DMG, DamageKind::Electric, shield);
             res += 1;
         }
         cmd.spawn((
             TeslaTimer(Timer::from_seconds(0.3, TimerMode::Once)),
             TeslaTargets(ents),
-            TeslaOrig(map.layout.hex_to_world_pos(pos.0)),
+            TeslaOrig(map.layout.hex_to_world_pos(node.pos)),
         ));
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(res as f32)));
+        Ok(Val::Number(res as f32))
     }
 }
 
@@ -916,569 +1147,1319 @@ fn tesla_anim(
     }
 }
 
-fn project_tick(
-    mut evt: TickEvts<Project>,
-    mut cmd: Commands,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&mut TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-    mut mats: Query<&mut Handle<ColorMaterial>>,
-    common: Res<CommonResources>,
-) {
-    for e in evt.read() {
+impl CyberNode for Project {
+    const ID: &'static str = "project";
+    const INPUTS: &'static [&'static str] = &["target", "enable"];
+    type Param = (
+        SCommands,
+        SRes<Map>,
+        SQuery<&'static mut TileType>,
+        SQuery<&'static mut Handle<ColorMaterial>>,
+        SRes<CommonResources>,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, map, tiles, mats, common): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking project tile");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            continue;
-        };
+        let pos = Hex::round(inputs.vec("target")?.to_array());
 
-        let Ok(Val::Vec(pos)) = fetch_port_data(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.transmute_lens(),
-        ) else {
-            continue;
-        };
-
-        let pos = Hex::round(pos.to_array());
         let Some(tilee) = map.storage.get(pos) else {
             error!("project tile: out of bounds");
-            let mut state = states.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            continue;
+            return Err(NodeError::OutOfBounds(pos));
         };
 
         let mut tt = tiles.get_mut(*tilee).unwrap();
         if *tt != TileType::Unoccupied {
             error!("project tile: tile already occupied");
-            let mut state = states.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            continue;
+            return Err(NodeError::Occupied(pos));
         }
 
         let ent = cmd.spawn((Health(10.), HexPos(pos))).id();
         *tt = TileType::Terrain(ent);
         *mats.get_mut(*tilee).unwrap() = common.illusion.clone();
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Empty));
+        Ok(Val::Empty)
     }
 }
 
-fn nearby_tick(
-    map: Res<Map>,
-    mut evt: TickEvts<NearbyEntity>,
-    mut states: Query<(&mut CyberState, &HexPos)>,
-    targets: Query<(Entity, &Transform), With<TargetableEntity>>,
-) {
-    for e in evt.read() {
+impl CyberNode for NearbyEntity {
+    const ID: &'static str = "nearby_entities";
+    type Param = (
+        SRes<Map>,
+        SQuery<(Entity, &'static Transform), With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        _inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking nearby entities");
-        let (mut state, hpos) = states.get_mut(e.e).unwrap();
-
         let targets = targets.iter().filter_map(|(e, trans)| {
             let pos = map.layout.world_pos_to_hex(trans.translation.xy());
-            (pos.distance_to(hpos.0) <= 8).then_some(e)
+            (pos.distance_to(node.pos) <= 8).then_some(e)
         });
 
-        *state = CyberState::Done(Ok(Val::List(targets.map(|e| Val::Entity(e)).collect())));
+        Ok(Val::List(targets.map(Val::Entity).collect()))
     }
 }
 
-fn closest_tick(
-    map: Res<Map>,
-    mut evt: EventReader<TickNode<ClosestEntity>>,
-    mut state: Query<(&mut CyberState, &HexPos)>,
-    targets: Query<(Entity, &Transform), With<TargetableEntity>>,
-) {
-    for tick in evt.read() {
+impl CyberNode for ClosestEntity {
+    const ID: &'static str = "closest_entity";
+    type Param = (
+        SRes<Map>,
+        SQuery<(Entity, &'static Transform), With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        _inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking get closest entity");
-        let (mut state, hpos) = state.get_mut(tick.e).unwrap();
         let e = targets.iter().find_map(|(e, trans)| {
             let pos = map.layout.world_pos_to_hex(trans.translation.xy());
-            (pos.distance_to(hpos.0) <= 8).then_some(e)
+            (pos.distance_to(node.pos) <= 8).then_some(e)
         });
 
-        *state = match e {
-            Some(e) => CyberState::Done(Ok(Val::Entity(e))),
+        match e {
+            Some(e) => Ok(Val::Entity(e)),
             None => {
                 error!("closest entity: no entities in range");
-                CyberState::Done(Err(()))
+                Err(NodeError::NoTarget)
             }
-        };
+        }
     }
 }
 
-fn constant_tick(mut evt: TickEvts<ConstantNumber>, mut state: Query<(&mut CyberState, &PortCfg)>) {
-    for tick in evt.read() {
-        info!("ticking constant number");
-        let Ok((mut state, cfg)) = state.get_mut(tick.e) else {
-            // node removed
-            continue;
-        };
+impl CyberNode for ConstantNumber {
+    const ID: &'static str = "constant_number";
+    type Param = ();
 
-        let Some(constant) = cfg.constant.as_ref() else {
+    fn evaluate(node: &NodeCtx, _inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking constant number");
+        let Some(constant) = node.constant.as_ref() else {
             error!("constant number: no constant configured");
-            *state = CyberState::Done(Err(()));
-            continue;
+            return Err(NodeError::NoConstant);
         };
 
-        *state = CyberState::Done(Ok(constant.clone()));
+        Ok(constant.clone())
     }
 }
 
-fn listlen_tick(
-    mut evt: TickEvts<ListLen>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking list length");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
-        };
+impl CyberNode for ListLen {
+    const ID: &'static str = "list_len";
+    const INPUTS: &'static [&'static str] = &["list"];
+    type Param = ();
 
-        let Ok(Val::List(list)) = fetch_port_data(
-            "list",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("list len: no input list");
-            let mut state = states.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            return;
-        };
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking list length");
+        let list = inputs.list("list")?;
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(list.len() as f32)));
+        Ok(Val::Number(list.len() as f32))
     }
 }
 
-fn list_tick(
-    mut evt: EventReader<TickNode<List>>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut state: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking construct list");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            continue;
-        };
-
-        let ports = ["a", "b", "c", "d", "e"].iter().filter_map(|name| {
-            port_by_name(
-                name,
-                pos.0,
-                &map,
-                cfg,
-                &mut metas.as_query_lens(),
-                &mut tiles.as_query_lens(),
-            )
-        });
+impl CyberNode for List {
+    const ID: &'static str = "list";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
 
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking construct list");
         let mut list = Vec::new();
-        for p in ports {
-            let Ok(target_state) = state.get(p) else {
-                warn!("list: port tile no longer exists");
+        for name in VARIADIC {
+            let Some(val) = inputs.optional(name) else {
                 continue;
             };
 
-            let CyberState::Done(Ok(val)) = target_state else {
+            let Ok(val) = val else {
                 warn!("list: input tile errored");
                 continue;
             };
 
             match val {
                 Val::Empty => continue,
-                Val::Entity(e) => list.push(Val::Entity(*e)),
-                Val::Vec(v) => list.push(Val::Vec(*v)),
-                Val::Number(n) => list.push(Val::Number(*n)),
-                Val::Text(t) => list.push(Val::Text(t.to_string())),
                 Val::List(l) => {
                     for i in l {
                         list.push(i.clone());
                     }
                 }
+                v => list.push(v.clone()),
             }
         }
 
-        let mut state = state.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::List(list)));
+        Ok(Val::List(list))
     }
 }
 
-fn entity_dir_tick(
-    mut evt: TickEvts<EntityDirection>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-    targets: Query<(&Transform, &PathfindPath), With<TargetableEntity>>,
-) {
-    for e in evt.read() {
+impl CyberNode for EntityDirection {
+    const ID: &'static str = "entity_direction";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = (
+        SRes<Map>,
+        SQuery<(&'static Transform, &'static PathfindPath), With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking entity direction");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
-        };
-
-        let Ok(Val::Entity(te)) = fetch_port_data(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("entity direction: 'target' port not configured");
-            let mut state = states.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            return;
-        };
+        let te = inputs.entity("target")?;
 
         let Ok((pos, path)) = targets.get(te) else {
             warn!("entity dir: target no longer exists");
-            continue;
+            return Err(NodeError::TargetGone);
+        };
+
+        // enemies at the end of their path stand still
+        let Some(next) = path.next() else {
+            return Ok(Val::Vec(Vec2::ZERO));
         };
 
-        let to = map.layout.hex_to_world_pos(path.path[path.i]);
+        let to = map.layout.hex_to_world_pos(next);
         let dir = (to - pos.translation.xy()).normalize_or_zero();
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Vec(dir)));
+        Ok(Val::Vec(dir))
     }
 }
 
-fn entity_pos_tick(
-    mut evt: TickEvts<EntityPos>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut state: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-    targets: Query<&Transform, With<TargetableEntity>>,
-) {
-    for e in evt.read() {
+impl CyberNode for EntityPos {
+    const ID: &'static str = "entity_pos";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = (
+        SRes<Map>,
+        SQuery<&'static Transform, With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
         info!("ticking entity position");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
-        };
+        let target = inputs.entity("target")?;
 
-        let Some(target) = port_by_name(
-            "target",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("entity position: 'target' port not configured");
-            let mut state = state.get_mut(e.e).unwrap();
-            *state = CyberState::Done(Err(()));
-            return;
-        };
-
-        let Ok(target_state) = state.get(target) else {
-            warn!("entity position: port tile doesnt exist");
-            continue;
+        let Ok(transform) = targets.get(target) else {
+            // entity no longer exists
+            warn!("entity position: target no longer exists");
+            return Err(NodeError::TargetGone);
         };
 
-        let CyberState::Done(Ok(Val::Entity(target))) = *target_state else {
-            warn!("entity position: 'target' port tile with error");
-            continue;
+        Ok(Val::Vec(
+            map.layout
+                .world_pos_to_fract_hex(transform.translation.xy()),
+        ))
+    }
+}
+
+impl CyberNode for Debug {
+    const ID: &'static str = "debug";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking debug");
+        for input in inputs.values(VARIADIC) {
+            info!("debug input: {:?}", input);
+        }
+
+        Ok(Val::Empty)
+    }
+}
+
+impl CyberNode for VectorNeg {
+    const ID: &'static str = "vector_neg";
+    const INPUTS: &'static [&'static str] = &["vector"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector negate");
+        let x = inputs.vec("vector")?;
+
+        Ok(Val::Vec(-x))
+    }
+}
+
+impl CyberNode for VectorLen {
+    const ID: &'static str = "vector_len";
+    const INPUTS: &'static [&'static str] = &["vector"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector len");
+        let x = inputs.vec("vector")?;
+
+        Ok(Val::Number(x.length()))
+    }
+}
+
+impl CyberNode for Vector {
+    const ID: &'static str = "vector";
+    const INPUTS: &'static [&'static str] = &["x", "y"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector create");
+        let x = inputs.number("x")?;
+        let y = inputs.number("y")?;
+
+        Ok(Val::Vec(vec2(x, y)))
+    }
+}
+
+impl CyberNode for NumberSub {
+    const ID: &'static str = "number_sub";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number subtract");
+        let res = numbers(inputs).reduce(|acc, v| acc - v).unwrap_or(0.);
+
+        Ok(Val::Number(res))
+    }
+}
+
+impl CyberNode for NumberMul {
+    const ID: &'static str = "number_mul";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number multiply");
+        let res = numbers(inputs).reduce(|acc, v| acc * v).unwrap_or(0.);
+
+        Ok(Val::Number(res))
+    }
+}
+
+impl CyberNode for VectorMul {
+    const ID: &'static str = "vector_mul";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector multiply");
+        let res = vectors(inputs)
+            .reduce(|acc, v| acc * v)
+            .unwrap_or(Vec2::splat(0.));
+
+        Ok(Val::Vec(res))
+    }
+}
+
+impl CyberNode for NumberAdd {
+    const ID: &'static str = "number_add";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number add");
+        Ok(Val::Number(numbers(inputs).sum()))
+    }
+}
+
+impl CyberNode for NumberDiv {
+    const ID: &'static str = "number_div";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number divide");
+        let mut res = None;
+        for name in VARIADIC {
+            let Some(Ok(Val::Number(x))) = inputs.optional(name) else {
+                continue;
+            };
+            res = match res {
+                None => Some(*x),
+                Some(_) if *x == 0. => {
+                    warn!("number divide: port {:?} is zero", name);
+                    return Err(NodeError::OutOfRange {
+                        port: name.to_string(),
+                        value: *x,
+                    });
+                }
+                Some(acc) => Some(acc / x),
+            };
+        }
+
+        Ok(Val::Number(res.unwrap_or(0.)))
+    }
+}
+
+impl CyberNode for NumberMin {
+    const ID: &'static str = "number_min";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number min");
+        let res = numbers(inputs).reduce(f32::min).unwrap_or(0.);
+
+        Ok(Val::Number(res))
+    }
+}
+
+impl CyberNode for NumberMax {
+    const ID: &'static str = "number_max";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number max");
+        let res = numbers(inputs).reduce(f32::max).unwrap_or(0.);
+
+        Ok(Val::Number(res))
+    }
+}
+
+impl CyberNode for NumberAbs {
+    const ID: &'static str = "number_abs";
+    const INPUTS: &'static [&'static str] = &["number"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number abs");
+        let x = inputs.number("number")?;
+
+        Ok(Val::Number(x.abs()))
+    }
+}
+
+impl CyberNode for VectorAdd {
+    const ID: &'static str = "vector_add";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector add");
+        Ok(Val::Vec(vectors(inputs).sum()))
+    }
+}
+
+impl CyberNode for VectorSub {
+    const ID: &'static str = "vector_sub";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector subtract");
+        let res = vectors(inputs)
+            .reduce(|acc, v| acc - v)
+            .unwrap_or(Vec2::ZERO);
+
+        Ok(Val::Vec(res))
+    }
+}
+
+impl CyberNode for VectorDot {
+    const ID: &'static str = "vector_dot";
+    const INPUTS: &'static [&'static str] = &["a", "b"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector dot");
+        let a = inputs.vec("a")?;
+        let b = inputs.vec("b")?;
+
+        Ok(Val::Number(a.dot(b)))
+    }
+}
+
+impl CyberNode for VectorNormalize {
+    const ID: &'static str = "vector_normalize";
+    const INPUTS: &'static [&'static str] = &["vector"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector normalize");
+        let x = inputs.vec("vector")?;
+
+        // a zero vector has no direction and stays zero
+        Ok(Val::Vec(x.normalize_or_zero()))
+    }
+}
+
+impl CyberNode for VectorRotate {
+    const ID: &'static str = "vector_rotate";
+    const INPUTS: &'static [&'static str] = &["vector", "angle"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking vector rotate");
+        let x = inputs.vec("vector")?;
+        let angle = inputs.number("angle")?;
+
+        // counter clockwise, in degrees
+        Ok(Val::Vec(Vec2::from_angle(angle.to_radians()).rotate(x)))
+    }
+}
+
+impl CyberNode for ListSum {
+    const ID: &'static str = "list_sum";
+    const INPUTS: &'static [&'static str] = &["list"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking list sum");
+        let res = inputs
+            .list("list")?
+            .iter()
+            .filter_map(|v| match v {
+                Val::Number(x) => Some(*x),
+                _ => None,
+            })
+            .sum::<f32>();
+
+        Ok(Val::Number(res))
+    }
+}
+
+impl CyberNode for ConstantBool {
+    const ID: &'static str = "constant_bool";
+    type Param = ();
+
+    fn evaluate(node: &NodeCtx, _inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking constant bool");
+        let Some(constant) = node.constant.as_ref() else {
+            error!("constant bool: no constant configured");
+            return Err(NodeError::NoConstant);
         };
 
-        let mut state = state.get_mut(e.e).unwrap();
-        let Ok(transform) = targets.get(target) else {
-            // entity no longer exists
-            warn!("entity position: target no longer exists");
-            *state = CyberState::Done(Err(()));
-            continue;
+        Ok(constant.clone())
+    }
+}
+
+impl CyberNode for NumberLess {
+    const ID: &'static str = "number_less";
+    const INPUTS: &'static [&'static str] = &["a", "b"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number less");
+        let a = inputs.number("a")?;
+        let b = inputs.number("b")?;
+
+        Ok(Val::Bool(a < b))
+    }
+}
+
+impl CyberNode for NumberGreater {
+    const ID: &'static str = "number_greater";
+    const INPUTS: &'static [&'static str] = &["a", "b"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number greater");
+        let a = inputs.number("a")?;
+        let b = inputs.number("b")?;
+
+        Ok(Val::Bool(a > b))
+    }
+}
+
+// default tolerance of the equal node, enough to hide float rounding
+const EQUAL_EPSILON: f32 = 0.0001;
+
+impl CyberNode for NumberEqual {
+    const ID: &'static str = "number_equal";
+    const INPUTS: &'static [&'static str] = &["a", "b", "epsilon"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking number equal");
+        let a = inputs.number("a")?;
+        let b = inputs.number("b")?;
+        let epsilon = match inputs.optional("epsilon") {
+            None => EQUAL_EPSILON,
+            Some(_) => inputs.number("epsilon")?.abs(),
         };
 
-        *state = CyberState::Done(Ok(Val::Vec(
-            map.layout
-                .world_pos_to_fract_hex(transform.translation.xy()),
-        )));
+        Ok(Val::Bool((a - b).abs() <= epsilon))
     }
 }
 
-fn debug_tick(
-    mut evt: TickEvts<Debug>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking debug");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for BoolAnd {
+    const ID: &'static str = "bool_and";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking bool and");
+        let res = bools(inputs).reduce(|acc, v| acc && v).unwrap_or(false);
+
+        Ok(Val::Bool(res))
+    }
+}
+
+impl CyberNode for BoolOr {
+    const ID: &'static str = "bool_or";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking bool or");
+        let res = bools(inputs).reduce(|acc, v| acc || v).unwrap_or(false);
+
+        Ok(Val::Bool(res))
+    }
+}
+
+impl CyberNode for BoolXor {
+    const ID: &'static str = "bool_xor";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking bool xor");
+        let res = bools(inputs).reduce(|acc, v| acc ^ v).unwrap_or(false);
+
+        Ok(Val::Bool(res))
+    }
+}
+
+impl CyberNode for BoolNot {
+    const ID: &'static str = "bool_not";
+    const INPUTS: &'static [&'static str] = &["bool"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking bool not");
+        let x = inputs.bool("bool")?;
+
+        Ok(Val::Bool(!x))
+    }
+}
+
+impl CyberNode for Select {
+    const ID: &'static str = "select";
+    const INPUTS: &'static [&'static str] = &["condition", "then", "else"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking select");
+        let port = if inputs.bool("condition")? {
+            "then"
+        } else {
+            "else"
         };
-        let ports = ["a", "b", "c", "d", "e"];
-        let inputs = ports.iter().flat_map(|p| {
-            fetch_port_data(
-                p,
-                pos.0,
-                &map,
-                cfg,
-                &mut metas.as_query_lens(),
-                &mut states.transmute_lens(),
-                &mut tiles.as_query_lens(),
-            )
-            .ok()
+
+        // the branch that is not picked may error, an unwired one is empty
+        match inputs.optional(port) {
+            None => Ok(Val::Empty),
+            Some(v) => v.cloned(),
+        }
+    }
+}
+
+// the entities of a list, anything else is dropped
+fn entities(list: &[Val]) -> impl Iterator<Item = Entity> + '_ {
+    list.iter().filter_map(|v| match v {
+        Val::Entity(e) => Some(*e),
+        _ => None,
+    })
+}
+
+// the `count` port of first / last, 1 if not connected
+fn list_count(inputs: &NodeInputs) -> Result<usize, NodeError> {
+    let Some(_) = inputs.optional("count") else {
+        return Ok(1);
+    };
+
+    let n = inputs.number("count")?;
+    if n.round() < 0. {
+        return Err(NodeError::OutOfRange {
+            port: "count".to_string(),
+            value: n,
         });
+    }
+    Ok(n.round() as usize)
+}
 
-        for input in inputs {
-            info!("debug input: {:?}", input);
+impl CyberNode for ListIndex {
+    const ID: &'static str = "list_index";
+    const INPUTS: &'static [&'static str] = &["list", "index"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking list index");
+        let list = inputs.list("list")?;
+        let n = inputs.number("index")?;
+
+        // negative indices count from the end
+        let i = n.round() as isize;
+        let i = if i < 0 { list.len() as isize + i } else { i };
+        match usize::try_from(i).ok().and_then(|i| list.get(i)) {
+            Some(v) => Ok(v.clone()),
+            None => {
+                warn!("list index: {} is out of range", n);
+                Err(NodeError::OutOfRange {
+                    port: "index".to_string(),
+                    value: n,
+                })
+            }
         }
+    }
+}
+
+impl CyberNode for ListFirst {
+    const ID: &'static str = "list_first";
+    const INPUTS: &'static [&'static str] = &["list", "count"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking list first");
+        let list = inputs.list("list")?;
+        let n = list_count(inputs)?.min(list.len());
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Empty));
+        Ok(Val::List(list[..n].to_vec()))
     }
 }
 
-fn vecneg_tick(
-    mut evt: TickEvts<VectorNeg>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking vector negate");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for ListLast {
+    const ID: &'static str = "list_last";
+    const INPUTS: &'static [&'static str] = &["list", "count"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking list last");
+        let list = inputs.list("list")?;
+        let n = list_count(inputs)?.min(list.len());
+
+        Ok(Val::List(list[list.len() - n..].to_vec()))
+    }
+}
+
+impl CyberNode for ListSortDistance {
+    const ID: &'static str = "list_sort_distance";
+    const INPUTS: &'static [&'static str] = &["list"];
+    type Param = (
+        SRes<Map>,
+        SQuery<&'static Transform, With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking list sort by distance");
+        let here = map.layout.hex_to_world_pos(node.pos);
+        let mut sorted: Vec<_> = entities(inputs.list("list")?)
+            .filter_map(|e| {
+                let trans = targets.get(e).ok()?;
+                Some((trans.translation.xy().distance(here), e))
+            })
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Val::List(
+            sorted.into_iter().map(|(_, e)| Val::Entity(e)).collect(),
+        ))
+    }
+}
+
+impl CyberNode for ListSortHealth {
+    const ID: &'static str = "list_sort_health";
+    const INPUTS: &'static [&'static str] = &["list"];
+    type Param = SQuery<&'static Health, With<TargetableEntity>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        targets: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking list sort by health");
+        let mut sorted: Vec<_> = entities(inputs.list("list")?)
+            .filter_map(|e| Some((targets.get(e).ok()?.0, e)))
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Val::List(
+            sorted.into_iter().map(|(_, e)| Val::Entity(e)).collect(),
+        ))
+    }
+}
+
+impl CyberNode for ListFilterHealth {
+    const ID: &'static str = "list_filter_health";
+    const INPUTS: &'static [&'static str] = &["list", "min", "max"];
+    type Param = SQuery<&'static Health, With<TargetableEntity>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        targets: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking list filter by health");
+        let min = match inputs.optional("min") {
+            None => f32::NEG_INFINITY,
+            Some(_) => inputs.number("min")?,
+        };
+        let max = match inputs.optional("max") {
+            None => f32::INFINITY,
+            Some(_) => inputs.number("max")?,
         };
 
-        let Ok(Val::Vec(x)) = fetch_port_data(
-            "vector",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("vector len: no or invalid x port");
-            continue;
+        let filtered = entities(inputs.list("list")?)
+            .filter(|e| targets.get(*e).is_ok_and(|hp| (min..=max).contains(&hp.0)))
+            .map(Val::Entity);
+
+        Ok(Val::List(filtered.collect()))
+    }
+}
+
+impl CyberNode for ListFilterArchetype {
+    const ID: &'static str = "list_filter_archetype";
+    const INPUTS: &'static [&'static str] = &["list", "archetype"];
+    type Param = (
+        SRes<Archetypes>,
+        SQuery<Option<&'static Archetype>, With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (archetypes, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking list filter by archetype");
+        let name = inputs.text("archetype")?;
+        let archetype = Archetype::new(name.trim());
+        if !archetypes.0.contains_key(&archetype) {
+            return Err(NodeError::UnknownName {
+                port: "archetype".to_string(),
+                name: name.to_string(),
+            });
+        }
+
+        let filtered = entities(inputs.list("list")?)
+            .filter(|e| {
+                targets
+                    .get(*e)
+                    .is_ok_and(|a| a.cloned().unwrap_or_default() == archetype)
+            })
+            .map(Val::Entity);
+
+        Ok(Val::List(filtered.collect()))
+    }
+}
+
+impl CyberNode for ListPositions {
+    const ID: &'static str = "list_positions";
+    const INPUTS: &'static [&'static str] = &["list"];
+    type Param = (
+        SRes<Map>,
+        SQuery<&'static Transform, With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking list positions");
+        // same coordinates as the entity position node
+        let positions = entities(inputs.list("list")?)
+            .filter_map(|e| targets.get(e).ok())
+            .map(|trans| Val::Vec(map.layout.world_pos_to_fract_hex(trans.translation.xy())));
+
+        Ok(Val::List(positions.collect()))
+    }
+}
+
+impl ExclusiveCyberNode for ListMap {
+    const ID: &'static str = "list_map";
+    const INPUTS: &'static [&'static str] = &["list", "node"];
+
+    fn evaluate(world: &mut World, node: &NodeCtx, inputs: &NodeInputs) -> Result<Val, NodeError> {
+        info!("ticking list map");
+        let list = inputs.list("list")?;
+        let id = inputs.text("node")?.trim();
+        if world.resource::<NodeRegistry>().applied_port(id).is_none() {
+            return Err(NodeError::UnknownName {
+                port: "node".to_string(),
+                name: id.to_string(),
+            });
+        }
+
+        // the node runs as if it was placed here, elements it fails on are dropped
+        let mut mapped = Vec::new();
+        for v in list.iter() {
+            let ctx = NodeCtx {
+                e: node.e,
+                pos: node.pos,
+                constant: None,
+            };
+            if let Some(Ok(v)) = apply_cyber_node(world, id, ctx, v.clone()) {
+                mapped.push(v);
+            }
+        }
+
+        Ok(Val::List(mapped))
+    }
+}
+
+// world distance between the centers of neighboring tiles
+fn tile_size(layout: &HexLayout) -> f32 {
+    layout
+        .hex_to_world_pos(Hex::new(1, 0))
+        .distance(layout.hex_to_world_pos(Hex::ZERO))
+}
+
+// enemies stand still while attacking and once they reached the end of their path
+fn moving(activity: Option<&EnemyActivity>, path: Option<&PathfindPath>) -> bool {
+    activity.is_some_and(|a| *a == EnemyActivity::Pathfinding)
+        && path.is_some_and(|p| p.next().is_some())
+}
+
+impl CyberNode for EntityHealth {
+    const ID: &'static str = "entity_health";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = SQuery<&'static Health, With<TargetableEntity>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        targets: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking entity health");
+        let target = inputs.entity("target")?;
+
+        let Ok(hp) = targets.get(target) else {
+            warn!("entity health: target no longer exists");
+            return Err(NodeError::TargetGone);
         };
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Vec(-x)));
+        Ok(Val::Number(hp.0))
     }
 }
 
-fn veclen_tick(
-    mut evt: TickEvts<VectorLen>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking vector len");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for EntityArchetype {
+    const ID: &'static str = "entity_archetype";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = SQuery<Option<&'static Archetype>, With<TargetableEntity>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        targets: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking entity archetype");
+        let target = inputs.entity("target")?;
+
+        let Ok(archetype) = targets.get(target) else {
+            warn!("entity archetype: target no longer exists");
+            return Err(NodeError::TargetGone);
         };
 
-        let Ok(Val::Vec(x)) = fetch_port_data(
-            "vector",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("vector len: no or invalid x port");
-            continue;
+        Ok(Val::Text(archetype.cloned().unwrap_or_default().0))
+    }
+}
+
+impl CyberNode for EntityDistance {
+    const ID: &'static str = "entity_distance";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = (
+        SRes<Map>,
+        SQuery<&'static Transform, With<TargetableEntity>>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking entity distance");
+        let target = inputs.entity("target")?;
+
+        let Ok(transform) = targets.get(target) else {
+            warn!("entity distance: target no longer exists");
+            return Err(NodeError::TargetGone);
         };
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(x.length())));
+        // in tiles, not rounded
+        let here = map.layout.hex_to_world_pos(node.pos);
+        let dist = transform.translation.xy().distance(here) / tile_size(&map.layout);
+
+        Ok(Val::Number(dist))
     }
 }
 
-fn vec_tick(
-    mut evt: TickEvts<Vector>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking vector create");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for EntitySpeed {
+    const ID: &'static str = "entity_speed";
+    const INPUTS: &'static [&'static str] = &["target"];
+    type Param = (
+        SRes<Map>,
+        SQuery<
+            (
+                Option<&'static EnemyActivity>,
+                Option<&'static PathfindPath>,
+                Option<&'static Speed>,
+            ),
+            With<TargetableEntity>,
+        >,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking entity speed");
+        let target = inputs.entity("target")?;
+
+        let Ok((activity, path, speed)) = targets.get(target) else {
+            warn!("entity speed: target no longer exists");
+            return Err(NodeError::TargetGone);
         };
 
-        let Ok(Val::Number(x)) = fetch_port_data(
-            "x",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("vector create: no or invalid x port");
-            continue;
+        // in tiles per second
+        let speed = match moving(activity, path) {
+            true => speed.map_or(enemy::SPEED, |s| s.0) / tile_size(&map.layout),
+            false => 0.,
         };
 
-        let Ok(Val::Number(y)) = fetch_port_data(
-            "y",
-            pos.0,
-            &map,
-            cfg,
-            &mut metas.as_query_lens(),
-            &mut states.transmute_lens(),
-            &mut tiles.as_query_lens(),
-        ) else {
-            error!("vector create: no or invalid y port");
-            continue;
+        Ok(Val::Number(speed))
+    }
+}
+
+impl CyberNode for EntityPredict {
+    const ID: &'static str = "entity_predict";
+    const INPUTS: &'static [&'static str] = &["target", "seconds"];
+    type Param = (
+        SRes<Map>,
+        SQuery<
+            (
+                &'static Transform,
+                Option<&'static EnemyActivity>,
+                Option<&'static PathfindPath>,
+                Option<&'static Speed>,
+            ),
+            With<TargetableEntity>,
+        >,
+    );
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        (map, targets): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking entity predict");
+        let target = inputs.entity("target")?;
+        // by default where an orbital strike fired now would hit
+        let secs = match inputs.optional("seconds") {
+            None => ORBITAL_DELAY,
+            Some(_) => inputs.number("seconds")?.max(0.),
         };
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Vec(vec2(x, y))));
+        let Ok((transform, activity, path, speed)) = targets.get(target) else {
+            warn!("entity predict: target no longer exists");
+            return Err(NodeError::TargetGone);
+        };
+
+        let mut pos = transform.translation.xy();
+        if let (true, Some(path)) = (moving(activity, path), path) {
+            let speed = speed.map_or(enemy::SPEED, |s| s.0);
+            pos = path.walk(&map.layout, pos, speed * secs);
+        }
+
+        Ok(Val::Vec(map.layout.world_pos_to_fract_hex(pos)))
     }
 }
 
-fn numsub_tick(
-    mut evt: TickEvts<NumberSub>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking number subtract");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for HeartHealth {
+    const ID: &'static str = "heart_health";
+    type Param = SQuery<&'static Health, With<Heartbeat>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        _inputs: &NodeInputs,
+        hearts: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking heart health");
+        let Ok(hp) = hearts.get_single() else {
+            warn!("heart health: no single heart on the map");
+            return Err(NodeError::NoTarget);
         };
-        let ports = ["a", "b", "c", "d", "e"];
-        let res = ports
-            .iter()
-            .flat_map(|p| {
-                fetch_port_data(
-                    p,
-                    pos.0,
-                    &map,
-                    cfg,
-                    &mut metas.as_query_lens(),
-                    &mut states.transmute_lens(),
-                    &mut tiles.as_query_lens(),
-                )
-                .ok()
-            })
-            .flat_map(|v| match v {
-                Val::Number(x) => Some(x),
-                _ => None,
-            })
-            .reduce(|acc, v| acc - v)
-            .unwrap_or(0.);
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(res)));
+        Ok(Val::Number(hp.0))
     }
 }
 
-fn nummul_tick(
-    mut evt: TickEvts<NumberMul>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
-) {
-    for e in evt.read() {
-        info!("ticking number multiply");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+impl CyberNode for HeartRate {
+    const ID: &'static str = "heart_rate";
+    type Param = SQuery<&'static Heartbeat>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        _inputs: &NodeInputs,
+        hearts: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking heart rate");
+        let Ok(beat) = hearts.get_single() else {
+            warn!("heart rate: no single heart on the map");
+            return Err(NodeError::NoTarget);
         };
-        let ports = ["a", "b", "c", "d", "e"];
-        let res = ports
-            .iter()
-            .flat_map(|p| {
-                fetch_port_data(
-                    p,
-                    pos.0,
-                    &map,
-                    cfg,
-                    &mut metas.as_query_lens(),
-                    &mut states.transmute_lens(),
-                    &mut tiles.as_query_lens(),
-                )
-                .ok()
-            })
-            .flat_map(|v| match v {
-                Val::Number(x) => Some(x),
-                _ => None,
-            })
-            .reduce(|acc, v| acc * v)
-            .unwrap_or(0.);
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Number(res)));
+        // heartbeats per second, like the cpu cycles in the hud
+        Ok(Val::Number(1. / beat.0.duration().as_secs_f32()))
+    }
+}
+
+impl CyberNode for TickCounter {
+    const ID: &'static str = "tick_counter";
+    type Param = SRes<Heartbeats>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        _inputs: &NodeInputs,
+        heartbeats: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking tick counter");
+        Ok(Val::Number(heartbeats.0 as f32))
+    }
+}
+
+impl CyberNode for SelfPosition {
+    const ID: &'static str = "self_position";
+    type Param = ();
+
+    fn evaluate(node: &NodeCtx, _inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking self position");
+        Ok(Val::Vec(vec2(node.pos.x as f32, node.pos.y as f32)))
+    }
+}
+
+impl CyberNode for EnemyCount {
+    const ID: &'static str = "enemy_count";
+    type Param = SQuery<(), With<TargetableEntity>>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        _inputs: &NodeInputs,
+        enemies: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking enemy count");
+        Ok(Val::Number(enemies.iter().count() as f32))
+    }
+}
+
+impl CyberNode for Random {
+    const ID: &'static str = "random";
+    const INPUTS: &'static [&'static str] = &["min", "max"];
+    type Param = SResMut<SimRng>;
+
+    fn evaluate(
+        _node: &NodeCtx,
+        inputs: &NodeInputs,
+        rng: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking random");
+        let min = match inputs.optional("min") {
+            None => 0.,
+            Some(_) => inputs.number("min")?,
+        };
+        let max = match inputs.optional("max") {
+            None => 1.,
+            Some(_) => inputs.number("max")?,
+        };
+
+        // shares the seeded rng with the enemy spawner, so a seeded run repeats
+        let t: f32 = rng.0.gen();
+        Ok(Val::Number(min + (max - min) * t))
+    }
+}
+
+impl CyberNode for ConstantText {
+    const ID: &'static str = "constant_text";
+    type Param = ();
+
+    fn evaluate(node: &NodeCtx, _inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking constant text");
+        let Some(constant) = node.constant.as_ref() else {
+            error!("constant text: no constant configured");
+            return Err(NodeError::NoConstant);
+        };
+
+        Ok(constant.clone())
+    }
+}
+
+impl CyberNode for TextConcat {
+    const ID: &'static str = "text_concat";
+    const INPUTS: &'static [&'static str] = VARIADIC;
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking text concat");
+        let text = inputs.values(VARIADIC).map(Val::to_string).collect();
+
+        Ok(Val::Text(text))
+    }
+}
+
+impl CyberNode for TextFormat {
+    const ID: &'static str = "text_format";
+    const INPUTS: &'static [&'static str] = &["format", "a", "b", "c", "d", "e"];
+    type Param = ();
+
+    fn evaluate(_node: &NodeCtx, inputs: &NodeInputs, _: &mut ()) -> Result<Val, NodeError> {
+        info!("ticking text format");
+        let format = inputs.text("format")?;
+
+        // every {} takes the next connected value, missing values are left empty
+        let mut values = inputs.values(VARIADIC);
+        let mut parts = format.split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            if let Some(v) = values.next() {
+                text.push_str(&v.to_string());
+            }
+            text.push_str(part);
+        }
+
+        Ok(Val::Text(text))
     }
 }
 
-fn vecmul_tick(
-    mut evt: TickEvts<VectorMul>,
-    map: Res<Map>,
-    node: Query<(&HexPos, &PortCfg)>,
-    mut tiles: Query<&TileType>,
-    mut states: Query<&mut CyberState>,
-    mut metas: Query<&PortMeta>,
+// world space text drawn over a display node
+#[derive(Component)]
+pub struct DisplayLabel(pub Entity);
+
+impl CyberNode for TextDisplay {
+    const ID: &'static str = "display";
+    const INPUTS: &'static [&'static str] = &["text"];
+    type Param = (
+        SCommands,
+        SRes<CommonResources>,
+        SRes<Map>,
+        SQuery<(&'static DisplayLabel, &'static mut Text)>,
+    );
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, common, map, labels): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        info!("ticking display");
+        let text = inputs.get("text")?.to_string();
+
+        let label = labels.iter_mut().find(|(l, _)| l.0 == node.e);
+        if let Some((_, mut label)) = label {
+            label.sections[0].value.clone_from(&text);
+        } else {
+            let pos = map.layout.hex_to_world_pos(node.pos);
+            cmd.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        text.clone(),
+                        TextStyle {
+                            font: common.font.clone(),
+                            font_size: 16.,
+                            ..default()
+                        },
+                    ),
+                    // above the tile, over everything else
+                    transform: Transform::from_translation(pos.extend(5.) + vec3(0., 30., 0.)),
+                    ..default()
+                },
+                DisplayLabel(node.e),
+            ));
+        }
+
+        Ok(Val::Text(text))
+    }
+}
+
+// labels of removed display nodes
+fn remove_display_labels(
+    mut cmd: Commands,
+    labels: Query<(Entity, &DisplayLabel)>,
+    nodes: Query<(), With<CyberState>>,
 ) {
-    for e in evt.read() {
-        info!("ticking vector multiply");
-        let Ok((pos, cfg)) = node.get(e.e) else {
-            return;
+    for (e, label) in labels.iter() {
+        if !nodes.contains(label.0) {
+            cmd.entity(e).despawn();
+        }
+    }
+}
+
+const STORAGE_SLOTS: [&str; 4] = ["0", "1", "2", "3"];
+
+// values kept by a storage node across heartbeats
+#[derive(Component)]
+pub struct StorageSlots(pub [Val; 4]);
+
+impl Default for StorageSlots {
+    fn default() -> Self {
+        Self([Val::Empty, Val::Empty, Val::Empty, Val::Empty])
+    }
+}
+
+impl CyberNode for Storage {
+    const ID: &'static str = "storage";
+    const INPUTS: &'static [&'static str] = &["slot"];
+    // data slots are stored after the heartbeat, so storage can feed a loop
+    const LATCHED: &'static [&'static str] = &STORAGE_SLOTS;
+    type Param = (SCommands, SQuery<&'static mut StorageSlots>);
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (_, storages): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError> {
+        let slot = match inputs.optional("slot") {
+            None => 0,
+            Some(_) => {
+                let n = inputs.number("slot")?;
+                if n.round() < 0. || n.round() as usize >= STORAGE_SLOTS.len() {
+                    error!("storage: 'slot' port is not in 0..=3");
+                    return Err(NodeError::OutOfRange {
+                        port: "slot".to_string(),
+                        value: n,
+                    });
+                }
+                n.round() as usize
+            }
         };
-        let ports = ["a", "b", "c", "d", "e"];
-        let res = ports
-            .iter()
-            .flat_map(|p| {
-                fetch_port_data(
-                    p,
-                    pos.0,
-                    &map,
-                    cfg,
-                    &mut metas.as_query_lens(),
-                    &mut states.transmute_lens(),
-                    &mut tiles.as_query_lens(),
-                )
-                .ok()
-            })
-            .flat_map(|v| match v {
-                Val::Vec(x) => Some(x),
-                _ => None,
-            })
-            .reduce(|acc, v| acc * v)
-            .unwrap_or(Vec2::splat(0.));
 
-        let mut state = states.get_mut(e.e).unwrap();
-        *state = CyberState::Done(Ok(Val::Vec(res)));
+        Ok(storages
+            .get(node.e)
+            .map(|s| s.0[slot].clone())
+            .unwrap_or(Val::Empty))
+    }
+
+    // every connected slot is overwritten with its input,
+    // empty or errored inputs keep whatever was stored before
+    fn latch(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        (cmd, storages): &mut SystemParamItem<Self::Param>,
+    ) {
+        let mut slots = storages
+            .get(node.e)
+            .map(|s| s.0.clone())
+            .unwrap_or_else(|_| StorageSlots::default().0);
+
+        for (i, name) in STORAGE_SLOTS.iter().enumerate() {
+            match inputs.optional(name) {
+                None | Some(Ok(Val::Empty)) => (),
+                Some(Ok(v)) => slots[i] = v.clone(),
+                Some(Err(_)) => warn!("storage: slot {:?} input errored, keeping old value", name),
+            }
+        }
+
+        if let Ok(mut storage) = storages.get_mut(node.e) {
+            storage.0 = slots;
+        } else {
+            cmd.entity(node.e).insert(StorageSlots(slots));
+        }
     }
 }
@@ -0,0 +1,194 @@
+use bevy::{
+    color::palettes::css::{GRAY, LIME, RED, YELLOW},
+    prelude::*,
+};
+
+use crate::{
+    nodes::{CyberState, HexPos, IconLabel, MetaLink, PortCfg, TargetableEntity, Val},
+    CommonResources, Debug, Map, TileType, HEX_SIZE,
+};
+
+// text drawn next to a node while the overlay is on
+#[derive(Component)]
+pub struct OverlayLabel(pub Entity);
+
+// the IconLabel of a placed node's item, drawn over its tile
+#[derive(Component)]
+pub struct NodeIconLabel(pub Entity);
+
+// F3 shows the values of all nodes and how they are wired
+pub fn overlay_keys(keys: Res<ButtonInput<KeyCode>>, mut dbg: ResMut<Debug>) {
+    if keys.just_pressed(KeyCode::F3) {
+        dbg.node_values = !dbg.node_values;
+        info!("node overlay: {}", dbg.node_values);
+    }
+}
+
+// the label of a node, vectors and entities are drawn instead
+pub fn overlay_text(state: &CyberState) -> String {
+    match state {
+        CyberState::Done(Ok(Val::List(l))) => format!("[{}]", l.len()),
+        CyberState::Done(Ok(Val::Entity(_))) => String::new(),
+        CyberState::Done(Ok(v)) => v.to_string(),
+        CyberState::Done(Err(_)) => "err".to_string(),
+        CyberState::Skipped => "skip".to_string(),
+        CyberState::Idle | CyberState::Disabled => String::new(),
+    }
+}
+
+fn state_color(state: Option<&CyberState>) -> Color {
+    match state {
+        Some(CyberState::Done(Ok(_))) => Color::from(LIME),
+        Some(CyberState::Done(Err(_))) => Color::from(RED),
+        _ => Color::from(GRAY),
+    }
+}
+
+pub fn draw_node_overlay(
+    mut gizmos: Gizmos,
+    dbg: Res<Debug>,
+    map: Res<Map>,
+    tiles: Query<&TileType>,
+    nodes: Query<(&HexPos, &PortCfg, &CyberState)>,
+    states: Query<&CyberState>,
+    targets: Query<&Transform, With<TargetableEntity>>,
+) {
+    if !dbg.node_values {
+        return;
+    }
+
+    for (pos, cfg, state) in nodes.iter() {
+        let center = map.layout.hex_to_world_pos(pos.0);
+
+        // one line per port, colored by what the feeding node did this heartbeat
+        for offset in cfg.inputs.keys() {
+            let from = pos.0 + *offset;
+            let upstream = map.storage.get(from).and_then(|te| match tiles.get(*te) {
+                Ok(TileType::CyberNode { e, .. }) => states.get(*e).ok(),
+                _ => None,
+            });
+            let start = map.layout.hex_to_world_pos(from);
+            let end = center + (start - center).normalize_or_zero() * HEX_SIZE.x * 0.4;
+            gizmos.arrow_2d(start, end, state_color(upstream));
+        }
+
+        match state {
+            CyberState::Done(Ok(Val::Vec(v))) => {
+                // in hex coordinates, like the positions the nodes output
+                let dir = map.layout.fract_hex_to_world_pos(*v)
+                    - map.layout.fract_hex_to_world_pos(Vec2::ZERO);
+                let end = center + dir.normalize_or_zero() * HEX_SIZE.x * 0.8;
+                gizmos.arrow_2d(center, end, Color::from(YELLOW));
+            }
+            CyberState::Done(Ok(Val::Entity(e))) => {
+                if let Ok(trans) = targets.get(*e) {
+                    gizmos.circle_2d(trans.translation.xy(), 15., Color::from(YELLOW));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// keeps one label per node while the overlay is on
+pub fn sync_overlay_labels(
+    mut cmd: Commands,
+    dbg: Res<Debug>,
+    map: Res<Map>,
+    common: Res<CommonResources>,
+    nodes: Query<(Entity, &HexPos, &CyberState)>,
+    mut labels: Query<(Entity, &OverlayLabel, &mut Text)>,
+) {
+    let mut labeled = Vec::new();
+    for (e, label, mut text) in labels.iter_mut() {
+        match nodes.get(label.0) {
+            Ok((_, _, state)) if dbg.node_values => {
+                let value = overlay_text(state);
+                if text.sections[0].value != value {
+                    text.sections[0].value = value;
+                }
+                labeled.push(label.0);
+            }
+            _ => cmd.entity(e).despawn(),
+        }
+    }
+
+    if !dbg.node_values {
+        return;
+    }
+
+    for (e, pos, state) in nodes.iter() {
+        if labeled.contains(&e) {
+            continue;
+        }
+        let at = map.layout.hex_to_world_pos(pos.0) + Vec2::new(0., -HEX_SIZE.y * 0.6);
+        cmd.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    overlay_text(state),
+                    TextStyle {
+                        font: common.font.clone(),
+                        font_size: 14.,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(at.extend(5.)),
+                ..default()
+            },
+            OverlayLabel(e),
+        ));
+    }
+}
+
+// keeps one icon label per placed node whose item has one
+pub fn sync_icon_labels(
+    mut cmd: Commands,
+    map: Res<Map>,
+    common: Res<CommonResources>,
+    nodes: Query<(Entity, &HexPos, &MetaLink)>,
+    icons: Query<&IconLabel>,
+    mut labels: Query<(Entity, &NodeIconLabel, &mut Text)>,
+) {
+    let icon = |e: Entity| {
+        let (_, _, meta) = nodes.get(e).ok()?;
+        icons.get(meta.0).ok()
+    };
+
+    let mut labeled = Vec::new();
+    for (e, label, mut text) in labels.iter_mut() {
+        match icon(label.0) {
+            Some(icon) => {
+                if text.sections[0].value != icon.0 {
+                    text.sections[0].value.clone_from(&icon.0);
+                }
+                labeled.push(label.0);
+            }
+            None => cmd.entity(e).despawn(),
+        }
+    }
+
+    for (e, pos, _) in nodes.iter() {
+        if labeled.contains(&e) {
+            continue;
+        }
+        let Some(icon) = icon(e) else {
+            continue;
+        };
+        let at = map.layout.hex_to_world_pos(pos.0);
+        cmd.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    icon.0.clone(),
+                    TextStyle {
+                        font: common.font.clone(),
+                        font_size: 18.,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(at.extend(4.)),
+                ..default()
+            },
+            NodeIconLabel(e),
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bevy::{
+    ecs::system::{lifetimeless::SCommands, SystemParamItem},
+    prelude::*,
+};
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::{
+    enemy::{PathfindPath, PathfindTarget, SPAWN_DISTANCE},
+    nodes::HexPos,
+    ron_asset::{RegisterRonAsset, RonAsset},
+    Heartbeat, Map, TileChanged, TileType,
+};
+
+pub const COSTS_PATH: &str = "enemy.costs.ron";
+
+// tile changes are batched, the fields are rebuilt at most this often
+const REPLAN_SECS: f32 = 0.25;
+
+pub struct PathingPlugin;
+impl Plugin for PathingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PathCosts>()
+            .register_ron_asset::<PathCosts>()
+            .init_resource::<FlowFields>()
+            .init_resource::<Replan>()
+            .add_systems(Update, mark_replan);
+    }
+}
+
+// what walking onto a tile costs, enemies walk around expensive tiles
+#[derive(Asset, Resource, Reflect, Deserialize, Clone, Debug, PartialEq)]
+#[reflect(Resource)]
+pub struct PathCosts {
+    // paid for every step so enemies take the shortest of the cheap paths
+    pub step: u32,
+    // also paid outside the map
+    pub unoccupied: u32,
+    pub heart: u32,
+    pub terrain: u32,
+    pub cyber_node: u32,
+}
+
+impl Default for PathCosts {
+    fn default() -> Self {
+        Self {
+            step: 1,
+            unoccupied: 0,
+            heart: 1,
+            terrain: 10,
+            cyber_node: 50,
+        }
+    }
+}
+
+impl PathCosts {
+    pub fn enter(&self, tt: Option<&TileType>) -> u32 {
+        let tile = match tt {
+            None | Some(TileType::Unoccupied) => self.unoccupied,
+            Some(TileType::Heart(_)) => self.heart,
+            Some(TileType::Terrain(_)) => self.terrain,
+            Some(TileType::CyberNode { .. }) => self.cyber_node,
+        };
+        // at least one so walking downhill always ends at the heart
+        self.step.max(1) + tile
+    }
+}
+
+// the cost of walking from every tile around a heart to it, and where to step next
+pub struct FlowField {
+    target: Hex,
+    radius: u32,
+    flow: HashMap<Hex, (u32, Hex)>,
+}
+
+impl FlowField {
+    // `cost` is what entering a tile costs
+    pub fn new(target: Hex, radius: u32, cost: impl Fn(Hex) -> u32) -> Self {
+        let mut field = Self {
+            target,
+            radius,
+            flow: HashMap::from([(target, (0, target))]),
+        };
+        field.spread([target], cost);
+        field
+    }
+
+    // dijkstra outwards from `from`, their costs have to be final already
+    fn spread(&mut self, from: impl IntoIterator<Item = Hex>, cost: impl Fn(Hex) -> u32) {
+        let mut open: BinaryHeap<_> = from
+            .into_iter()
+            .filter_map(|h| Some(Reverse((self.dist(h)?, h.x, h.y))))
+            .collect();
+        while let Some(Reverse((d, x, y))) = open.pop() {
+            let h = Hex::new(x, y);
+            if self.dist(h).is_some_and(|best| best < d) {
+                // already reached cheaper
+                continue;
+            }
+
+            // walking from a neighbor onto h
+            let nd = d + cost(h);
+            for n in h.all_neighbors() {
+                if n.unsigned_distance_to(self.target) > self.radius {
+                    continue;
+                }
+                if self.dist(n).map_or(true, |best| nd < best) {
+                    self.flow.insert(n, (nd, h));
+                    open.push(Reverse((nd, n.x, n.y)));
+                }
+            }
+        }
+    }
+
+    // what entering the `changed` tiles costs changed,
+    // only the tiles whose way leads through them are redone
+    pub fn update(&mut self, changed: &[Hex], cost: impl Fn(Hex) -> u32) {
+        let mut stale = HashSet::new();
+        let mut open: Vec<Hex> = changed.to_vec();
+        while let Some(h) = open.pop() {
+            for n in h.all_neighbors() {
+                if self.next(n) == Some(h) && stale.insert(n) {
+                    open.push(n);
+                }
+            }
+        }
+        for h in stale.iter() {
+            self.flow.remove(h);
+        }
+
+        // the tiles around the stale ones still know their way, and cheaper
+        // changed tiles might offer a better one to their neighbors
+        let edge: Vec<Hex> = stale
+            .iter()
+            .flat_map(|h| h.all_neighbors())
+            .chain(changed.iter().copied())
+            .collect();
+        self.spread(edge, cost);
+    }
+
+    pub fn dist(&self, h: Hex) -> Option<u32> {
+        self.flow.get(&h).map(|(d, _)| *d)
+    }
+
+    // the neighbor to walk to from `h`, `None` at the target or outside the field
+    pub fn next(&self, h: Hex) -> Option<Hex> {
+        let (_, next) = self.flow.get(&h)?;
+        (*next != h).then_some(*next)
+    }
+
+    // every tile from `from` to the target, both included
+    pub fn path(&self, from: Hex) -> Option<Vec<Hex>> {
+        self.dist(from)?;
+        let mut path = vec![from];
+        while let Some(next) = self.next(*path.last().unwrap()) {
+            path.push(next);
+        }
+        Some(path)
+    }
+}
+
+impl RonAsset for PathCosts {
+    const PATH: &'static str = COSTS_PATH;
+    const EXTENSIONS: &'static [&'static str] = &["costs.ron"];
+
+    type Param = SCommands;
+
+    // every field is rebuilt once the costs are inserted
+    fn apply(&self, cmd: &mut SystemParamItem<Self::Param>) {
+        info!("applying path costs {:?}", self);
+        cmd.insert_resource(self.clone());
+    }
+}
+
+// one field for every heart, shared by all enemies walking to it
+#[derive(Resource, Default)]
+pub struct FlowFields(pub HashMap<Entity, FlowField>);
+
+#[derive(Resource)]
+pub struct Replan {
+    // every field is built from scratch, after the costs changed
+    rebuild: bool,
+    // tiles whose type changed since the fields were updated
+    changed: HashSet<Hex>,
+    cooldown: Timer,
+}
+
+impl Default for Replan {
+    fn default() -> Self {
+        Self {
+            rebuild: true,
+            changed: HashSet::new(),
+            cooldown: Timer::from_seconds(REPLAN_SECS, TimerMode::Once),
+        }
+    }
+}
+
+fn mark_replan(
+    mut evt: EventReader<TileChanged>,
+    costs: Option<Res<PathCosts>>,
+    mut replan: ResMut<Replan>,
+) {
+    if costs.is_some_and(|c| c.is_changed()) {
+        replan.rebuild = true;
+    }
+
+    for changed in evt.read() {
+        replan.changed.extend(changed.tiles.iter().copied());
+    }
+}
+
+// updates the fields once the map changed and sends every enemy down its new path
+pub fn replan(
+    time: Res<Time>,
+    map: Res<Map>,
+    types: Query<&TileType>,
+    costs: Option<Res<PathCosts>>,
+    mut replan: ResMut<Replan>,
+    mut fields: ResMut<FlowFields>,
+    hearts: Query<(Entity, &HexPos), With<Heartbeat>>,
+    mut enemies: Query<(&PathfindTarget, &mut PathfindPath)>,
+) {
+    let Some(costs) = costs else {
+        // not loaded yet
+        return;
+    };
+
+    replan.cooldown.tick(time.delta());
+    let missing = hearts.iter().any(|(e, _)| !fields.0.contains_key(&e));
+    let dirty = replan.rebuild || !replan.changed.is_empty();
+    if !missing && !(dirty && replan.cooldown.finished()) {
+        return;
+    }
+
+    let cost = |h: Hex| costs.enter(map.storage.get(h).and_then(|e| types.get(*e).ok()));
+    if missing || replan.rebuild {
+        fields.0 = hearts
+            .iter()
+            .map(|(e, pos)| (e, FlowField::new(pos.0, SPAWN_DISTANCE, cost)))
+            .collect();
+    } else {
+        let changed: Vec<Hex> = replan.changed.iter().copied().collect();
+        for field in fields.0.values_mut() {
+            field.update(&changed, cost);
+        }
+    }
+    replan.rebuild = false;
+    replan.changed.clear();
+    replan.cooldown.reset();
+
+    for (target, mut path) in enemies.iter_mut() {
+        if let Some(field) = fields.0.get(&target.0) {
+            path.follow(field);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemId, SystemParam, SystemParamItem},
+    prelude::*,
+    utils::HashMap,
+};
+use hexx::Hex;
+
+use crate::{
+    nodes::{CyberState, HexPos, MetaLink, NodeError, NodeKind, PortCfg, PortMeta, Val, ValType},
+    Map, TileType,
+};
+
+// a node that can be placed on the map and is evaluated every heartbeat.
+// register it with `app.register_cyber_node::<T>()` and add an entry with
+// the same id to the node catalog to make it show up in the shop.
+pub(crate) trait CyberNode: Send + Sync + 'static {
+    // links the node to its catalog entry
+    const ID: &'static str;
+    // input ports that are read before evaluating
+    const INPUTS: &'static [&'static str] = &[];
+    // input ports that are only read by `latch`, after every node of the heartbeat
+    // was evaluated. they do not order the evaluation, so loops through them are fine
+    const LATCHED: &'static [&'static str] = &[];
+
+    type Param: SystemParam + 'static;
+
+    fn evaluate(
+        node: &NodeCtx,
+        inputs: &NodeInputs,
+        param: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Val, NodeError>;
+
+    fn latch(_node: &NodeCtx, _inputs: &NodeInputs, _param: &mut SystemParamItem<Self::Param>) {}
+}
+
+// a node that runs other nodes and so needs the whole world to evaluate.
+// register it with `app.register_exclusive_cyber_node::<T>()`
+pub(crate) trait ExclusiveCyberNode: Send + Sync + 'static {
+    const ID: &'static str;
+    const INPUTS: &'static [&'static str] = &[];
+
+    fn evaluate(world: &mut World, node: &NodeCtx, inputs: &NodeInputs) -> Result<Val, NodeError>;
+}
+
+pub(crate) trait RegisterCyberNode {
+    fn register_cyber_node<T: CyberNode>(&mut self) -> &mut Self;
+    fn register_exclusive_cyber_node<T: ExclusiveCyberNode>(&mut self) -> &mut Self;
+}
+
+impl RegisterCyberNode for App {
+    fn register_cyber_node<T: CyberNode>(&mut self) -> &mut Self {
+        let system = self.world_mut().register_system(evaluate_node::<T>);
+        let latch =
+            (!T::LATCHED.is_empty()).then(|| self.world_mut().register_system(latch_node::<T>));
+        self.world_mut()
+            .get_resource_or_insert_with(NodeRegistry::default)
+            .0
+            .insert(
+                T::ID,
+                RegisteredNode {
+                    inputs: T::INPUTS,
+                    latched: T::LATCHED,
+                    system,
+                    latch,
+                    exclusive: false,
+                },
+            );
+        self
+    }
+
+    fn register_exclusive_cyber_node<T: ExclusiveCyberNode>(&mut self) -> &mut Self {
+        let system = self
+            .world_mut()
+            .register_system(evaluate_exclusive_node::<T>);
+        self.world_mut()
+            .get_resource_or_insert_with(NodeRegistry::default)
+            .0
+            .insert(
+                T::ID,
+                RegisteredNode {
+                    inputs: T::INPUTS,
+                    latched: &[],
+                    system,
+                    latch: None,
+                    exclusive: true,
+                },
+            );
+        self
+    }
+}
+
+type NodeSystem = SystemId<(NodeCtx, NodeInputs), Result<Val, NodeError>>;
+type LatchSystem = SystemId<(NodeCtx, NodeInputs)>;
+
+struct RegisteredNode {
+    inputs: &'static [&'static str],
+    latched: &'static [&'static str],
+    system: NodeSystem,
+    latch: Option<LatchSystem>,
+    exclusive: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct NodeRegistry(HashMap<&'static str, RegisteredNode>);
+
+impl NodeRegistry {
+    // the ports a node reads while evaluating
+    pub fn inputs(&self, kind: &NodeKind) -> &'static [&'static str] {
+        self.0.get(kind.0.as_str()).map_or(&[], |n| n.inputs)
+    }
+
+    // the port a value is handed to when applying the node `id` to it,
+    // `None` for nodes without inputs, with latched ones or that run other nodes
+    pub fn applied_port(&self, id: &str) -> Option<&'static str> {
+        let n = self.0.get(id)?;
+        if n.exclusive || !n.latched.is_empty() {
+            return None;
+        }
+        n.inputs.first().copied()
+    }
+}
+
+// the node being evaluated
+pub struct NodeCtx {
+    pub e: Entity,
+    pub pos: Hex,
+    pub constant: Option<Val>,
+}
+
+// values of the wired up input ports, by port name
+pub struct NodeInputs(HashMap<&'static str, Result<Val, NodeError>>);
+
+impl NodeInputs {
+    // `None` if the port is not wired up
+    pub fn optional(&self, name: &str) -> Option<Result<&Val, NodeError>> {
+        self.0.get(name).map(|v| v.as_ref().map_err(Clone::clone))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Val, NodeError> {
+        let Some(v) = self.optional(name) else {
+            error!("{:?} port not configured", name);
+            return Err(NodeError::NotConfigured(name.to_string()));
+        };
+
+        if matches!(v, Err(ref e) if *e != NodeError::Skipped) {
+            warn!("port {:?} tile errored", name);
+        }
+        v
+    }
+
+    // nodes with an `enable` port only run while it is true
+    pub fn enabled(&self) -> Result<(), NodeError> {
+        match self.optional("enable") {
+            None => Ok(()),
+            Some(_) if self.bool("enable")? => Ok(()),
+            Some(_) => Err(NodeError::Skipped),
+        }
+    }
+
+    // values of all wired up ports that did not error, in port order
+    pub fn values<'a>(&'a self, names: &'a [&str]) -> impl Iterator<Item = &'a Val> {
+        names
+            .iter()
+            .filter_map(|name| self.optional(name).and_then(Result::ok))
+    }
+
+    pub fn number(&self, name: &str) -> Result<f32, NodeError> {
+        match self.get(name)? {
+            Val::Number(n) => Ok(*n),
+            v => Self::invalid(name, ValType::Number, v),
+        }
+    }
+
+    pub fn vec(&self, name: &str) -> Result<Vec2, NodeError> {
+        match self.get(name)? {
+            Val::Vec(v) => Ok(*v),
+            v => Self::invalid(name, ValType::Vec, v),
+        }
+    }
+
+    pub fn entity(&self, name: &str) -> Result<Entity, NodeError> {
+        match self.get(name)? {
+            Val::Entity(e) => Ok(*e),
+            v => Self::invalid(name, ValType::Entity, v),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Result<bool, NodeError> {
+        match self.get(name)? {
+            Val::Bool(b) => Ok(*b),
+            v => Self::invalid(name, ValType::Bool, v),
+        }
+    }
+
+    pub fn text(&self, name: &str) -> Result<&str, NodeError> {
+        match self.get(name)? {
+            Val::Text(t) => Ok(t),
+            v => Self::invalid(name, ValType::Text, v),
+        }
+    }
+
+    pub fn list(&self, name: &str) -> Result<&Vec<Val>, NodeError> {
+        match self.get(name)? {
+            Val::List(l) => Ok(l),
+            v => Self::invalid(name, ValType::List, v),
+        }
+    }
+
+    fn invalid<T>(name: &str, expected: ValType, v: &Val) -> Result<T, NodeError> {
+        warn!("port {:?} has invalid type: {:?}", name, v);
+        Err(NodeError::WrongType {
+            port: name.to_string(),
+            expected,
+            got: v.vt(),
+        })
+    }
+}
+
+fn evaluate_node<T: CyberNode>(
+    In((node, inputs)): In<(NodeCtx, NodeInputs)>,
+    mut param: StaticSystemParam<T::Param>,
+) -> Result<Val, NodeError> {
+    T::evaluate(&node, &inputs, &mut param)
+}
+
+fn evaluate_exclusive_node<T: ExclusiveCyberNode>(
+    In((node, inputs)): In<(NodeCtx, NodeInputs)>,
+    world: &mut World,
+) -> Result<Val, NodeError> {
+    T::evaluate(world, &node, &inputs)
+}
+
+fn latch_node<T: CyberNode>(
+    In((node, inputs)): In<(NodeCtx, NodeInputs)>,
+    mut param: StaticSystemParam<T::Param>,
+) {
+    T::latch(&node, &inputs, &mut param)
+}
+
+fn upstream_value(world: &World, map: &Map, port: &str, h: Hex) -> Result<Val, NodeError> {
+    let no_input = || NodeError::NoInput {
+        port: port.to_string(),
+        at: h,
+    };
+
+    let Some(tile) = map.storage.get(h) else {
+        warn!("port tile at {:?} is out of bounds", h);
+        return Err(no_input());
+    };
+
+    let Some(TileType::CyberNode { e, .. }) = world.get::<TileType>(*tile) else {
+        warn!("port tile at {:?} is not a cyber node", h);
+        return Err(no_input());
+    };
+
+    match world.get::<CyberState>(*e) {
+        Some(CyberState::Done(Ok(v))) => Ok(v.clone()),
+        Some(CyberState::Done(Err(e))) => Err(e.upstream(port, h)),
+        Some(CyberState::Skipped) => Err(NodeError::Skipped),
+        Some(_) => Err(NodeError::Upstream {
+            port: port.to_string(),
+            at: h,
+        }),
+        None => {
+            warn!("port tile at {:?} no longer exists", h);
+            Err(no_input())
+        }
+    }
+}
+
+fn gather_inputs(world: &World, node: &NodeCtx, names: &[&'static str]) -> NodeInputs {
+    let map = world.resource::<Map>();
+    let mut inputs = HashMap::new();
+    let Some(cfg) = world.get::<PortCfg>(node.e) else {
+        return NodeInputs(inputs);
+    };
+
+    for (h, pe) in cfg.inputs.iter() {
+        let Some(meta) = world.get::<PortMeta>(*pe) else {
+            continue;
+        };
+        let Some(name) = names.iter().find(|n| **n == meta.name) else {
+            continue;
+        };
+
+        inputs.insert(*name, upstream_value(world, map, name, node.pos + *h));
+    }
+
+    NodeInputs(inputs)
+}
+
+fn node_ctx(world: &World, e: Entity) -> Option<(NodeKind, NodeCtx)> {
+    let kind = world
+        .get::<MetaLink>(e)
+        .and_then(|ml| world.get::<NodeKind>(ml.0))?
+        .clone();
+    let (pos, cfg) = (world.get::<HexPos>(e)?, world.get::<PortCfg>(e)?);
+
+    let node = NodeCtx {
+        e,
+        pos: pos.0,
+        constant: cfg.constant.clone(),
+    };
+    Some((kind, node))
+}
+
+// evaluates a placed node with the current values of its inputs
+pub fn run_cyber_node(world: &mut World, e: Entity) {
+    let Some((kind, node)) = node_ctx(world, e) else {
+        // node removed
+        return;
+    };
+
+    let Some((system, names)) = world
+        .resource::<NodeRegistry>()
+        .0
+        .get(kind.0.as_str())
+        .map(|n| (n.system, n.inputs))
+    else {
+        debug!("no cyber node registered for {:?}", kind);
+        return;
+    };
+
+    let inputs = gather_inputs(world, &node, names);
+    let res = match inputs.enabled() {
+        Ok(()) => world
+            .run_system_with_input(system, (node, inputs))
+            .unwrap_or_else(|err| {
+                error!("failed to evaluate {:?}: {:?}", kind, err);
+                Err(NodeError::Internal)
+            }),
+        Err(e) => Err(e),
+    };
+
+    if let Some(mut state) = world.get_mut::<CyberState>(e) {
+        *state = match res {
+            Err(NodeError::Skipped) => CyberState::Skipped,
+            res => CyberState::Done(res),
+        };
+    }
+}
+
+// evaluates the node `id` for `node` with `input` on its first port and nothing
+// wired to the others. `None` if the node can't be applied like this
+pub fn apply_cyber_node(
+    world: &mut World,
+    id: &str,
+    node: NodeCtx,
+    input: Val,
+) -> Option<Result<Val, NodeError>> {
+    let registry = world.resource::<NodeRegistry>();
+    let port = registry.applied_port(id)?;
+    let system = registry.0.get(id)?.system;
+
+    let inputs = NodeInputs([(port, Ok(input))].into_iter().collect());
+    let res = world
+        .run_system_with_input(system, (node, inputs))
+        .unwrap_or_else(|err| {
+            error!("failed to apply {:?}: {:?}", id, err);
+            Err(NodeError::Internal)
+        });
+    Some(res)
+}
+
+// hands the latched inputs of a node over to it, once the heartbeat is evaluated
+pub fn latch_cyber_node(world: &mut World, e: Entity) {
+    let Some((kind, node)) = node_ctx(world, e) else {
+        return;
+    };
+
+    let Some((Some(system), names)) = world
+        .resource::<NodeRegistry>()
+        .0
+        .get(kind.0.as_str())
+        .map(|n| (n.latch, n.latched))
+    else {
+        return;
+    };
+
+    let inputs = gather_inputs(world, &node, names);
+    if let Err(err) = world.run_system_with_input(system, (node, inputs)) {
+        error!("failed to latch {:?}: {:?}", kind, err);
+    }
+}
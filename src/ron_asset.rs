@@ -0,0 +1,99 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::system::{StaticSystemParam, SystemParam, SystemParamItem},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+// game data kept in a ron file in the assets directory, like the node catalog.
+// register it with `app.register_ron_asset::<T>()`, it is loaded on startup and applied once
+// it is there. the files are hot reloaded, editing one applies it again, no recompile needed
+pub(crate) trait RonAsset: Asset + DeserializeOwned {
+    // relative to the assets directory
+    const PATH: &'static str;
+    // the loader is picked by the end of the file name, like "catalog.ron"
+    const EXTENSIONS: &'static [&'static str];
+
+    type Param: SystemParam + 'static;
+
+    // called every time the file was loaded or changed
+    fn apply(&self, param: &mut SystemParamItem<Self::Param>);
+}
+
+pub(crate) trait RegisterRonAsset {
+    fn register_ron_asset<T: RonAsset>(&mut self) -> &mut Self;
+}
+
+impl RegisterRonAsset for App {
+    fn register_ron_asset<T: RonAsset>(&mut self) -> &mut Self {
+        self.init_asset::<T>()
+            .register_asset_loader(RonAssetLoader::<T>(PhantomData))
+            .add_systems(Startup, load_asset::<T>)
+            .add_systems(Update, apply_asset::<T>)
+    }
+}
+
+// keeps the asset loaded, so it is reloaded when the file changes
+#[derive(Resource)]
+struct RonHandle<T: Asset>(Handle<T>);
+
+struct RonAssetLoader<T>(PhantomData<T>);
+
+#[derive(Debug, Error)]
+pub enum RonAssetError {
+    #[error("could not read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _ctx: &'a mut LoadContext<'_>,
+    ) -> Result<T, RonAssetError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+fn load_asset<T: RonAsset>(mut cmd: Commands, ass: Res<AssetServer>) {
+    cmd.insert_resource(RonHandle::<T>(ass.load(T::PATH)));
+}
+
+fn apply_asset<T: RonAsset>(
+    mut evt: EventReader<AssetEvent<T>>,
+    handle: Res<RonHandle<T>>,
+    assets: Res<Assets<T>>,
+    mut param: StaticSystemParam<T::Param>,
+) {
+    for e in evt.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = e else {
+            continue;
+        };
+
+        if *id != handle.0.id() {
+            continue;
+        }
+
+        let Some(asset) = assets.get(*id) else {
+            continue;
+        };
+
+        T::apply(asset, &mut param);
+    }
+}
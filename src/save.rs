@@ -0,0 +1,506 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use hexx::Hex;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    catalog::NodeItems,
+    deinit,
+    economy::Credits,
+    enemy::{Archetype, Archetypes, Dying, EnemyBundle, EnemyRes, PathfindPath},
+    nodes::{
+        Health, HexPos, NodeBundle, NodeKind, PlasmaCounter, PortCfg, PortMeta, PortMetas,
+        StorageSlots, TargetableEntity, Val,
+    },
+    scheduler::Heartbeats,
+    waves::WaveDirector,
+    Appstate, CommonResources, Gamestate, HealTimer, HeartBundle, Heartbeat, Map, TileType,
+};
+
+const SAVE_VERSION: u32 = 1;
+const QUICKSAVE_PATH: &str = "saves/quicksave.ron";
+const AUTOSAVE_PATH: &str = "saves/autosave.ron";
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .init_resource::<SaveSettings>()
+            .add_systems(
+                PreUpdate,
+                (save_game, load_game)
+                    .chain()
+                    .run_if(in_state(Gamestate::Game)),
+            )
+            // leaving the game means the heart died, save before the map is torn down
+            .add_systems(OnExit(Appstate::Game), autosave.before(deinit));
+    }
+}
+
+#[derive(Event)]
+pub struct SaveGame(pub PathBuf);
+
+#[derive(Event)]
+pub struct LoadGame(pub PathBuf);
+
+#[derive(Resource)]
+pub struct SaveSettings {
+    // where the board is saved when the heart is destroyed, for post-mortems
+    pub autosave: Option<PathBuf>,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            autosave: Some(AUTOSAVE_PATH.into()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write save file: {0}")]
+    Ser(#[from] ron::Error),
+    #[error("could not parse save file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("save file has version {0}, expected {}", SAVE_VERSION)]
+    Version(u32),
+}
+
+pub type SavedHex = [i32; 2];
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    // elapsed seconds of the heart heal timer
+    heal: f32,
+    #[serde(default)]
+    heartbeats: u64,
+    // saves from before the economy start with the starting credits
+    #[serde(default)]
+    credits: Option<u32>,
+    #[serde(default)]
+    waves: WaveDirector,
+    tiles: Vec<SavedTile>,
+    enemies: Vec<SavedEnemy>,
+}
+
+// only occupied tiles are saved, everything else loads as unoccupied
+#[derive(Serialize, Deserialize)]
+struct SavedTile {
+    tile: SavedHex,
+    content: SavedContent,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SavedContent {
+    Terrain {
+        hp: f32,
+    },
+    Heart {
+        hp: f32,
+        beat: f32,
+        beat_duration: f32,
+    },
+    Node {
+        node: NodeKind,
+        hp: f32,
+        // offset to the input node and the name of the port it is wired to
+        inputs: Vec<(SavedHex, String)>,
+        constant: Option<SavedVal>,
+        storage: Option<Vec<SavedVal>>,
+        plasma: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEnemy {
+    pos: [f32; 2],
+    hp: f32,
+    #[serde(default)]
+    archetype: Archetype,
+    path: Vec<SavedHex>,
+    i: usize,
+}
+
+// entities are saved as an index into the saved enemies,
+// anything else they point to is gone after loading
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SavedVal {
+    Empty,
+    Enemy(usize),
+    Vec([f32; 2]),
+    Number(f32),
+    Text(String),
+    List(Vec<SavedVal>),
+    Bool(bool),
+}
+
+impl SavedVal {
+    pub fn new(v: &Val, enemies: &HashMap<Entity, usize>) -> Self {
+        match v {
+            Val::Empty => Self::Empty,
+            Val::Entity(e) => enemies.get(e).map_or(Self::Empty, |i| Self::Enemy(*i)),
+            Val::Vec(v) => Self::Vec(v.to_array()),
+            Val::Number(n) => Self::Number(*n),
+            Val::Text(t) => Self::Text(t.clone()),
+            Val::List(l) => Self::List(l.iter().map(|v| Self::new(v, enemies)).collect()),
+            Val::Bool(b) => Self::Bool(*b),
+        }
+    }
+
+    pub fn val(&self, enemies: &[Entity]) -> Val {
+        match self {
+            Self::Empty => Val::Empty,
+            Self::Enemy(i) => enemies.get(*i).map_or(Val::Empty, |e| Val::Entity(*e)),
+            Self::Vec(v) => Val::Vec(Vec2::from_array(*v)),
+            Self::Number(n) => Val::Number(*n),
+            Self::Text(t) => Val::Text(t.clone()),
+            Self::List(l) => Val::List(l.iter().map(|v| v.val(enemies)).collect()),
+            Self::Bool(b) => Val::Bool(*b),
+        }
+    }
+}
+
+pub fn saved_hex(h: Hex) -> SavedHex {
+    [h.x, h.y]
+}
+
+pub fn hex(h: SavedHex) -> Hex {
+    Hex::new(h[0], h[1])
+}
+
+fn map_tiles(map: &Map) -> Vec<(Hex, Entity)> {
+    let bounds = map.storage.bounds();
+    bounds
+        .center
+        .range(bounds.radius)
+        .filter_map(|h| Some((h, *map.storage.get(h)?)))
+        .collect()
+}
+
+pub fn save(world: &mut World, path: &Path) -> Result<(), SaveError> {
+    let file = snapshot(world);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(&file, PrettyConfig::default())?,
+    )?;
+    Ok(())
+}
+
+pub fn load(world: &mut World, path: &Path) -> Result<(), SaveError> {
+    let file: SaveFile = ron::de::from_bytes(&fs::read(path)?)?;
+    if file.version != SAVE_VERSION {
+        return Err(SaveError::Version(file.version));
+    }
+    restore(world, file);
+    Ok(())
+}
+
+fn snapshot(world: &mut World) -> SaveFile {
+    let enemies: Vec<_> = world
+        .query_filtered::<(
+            Entity,
+            &Transform,
+            &Health,
+            &PathfindPath,
+            Option<&Archetype>,
+        ), With<TargetableEntity>>()
+        .iter(world)
+        .map(|(e, trans, hp, path, archetype)| {
+            let enemy = SavedEnemy {
+                pos: trans.translation.xy().to_array(),
+                hp: hp.0,
+                archetype: archetype.cloned().unwrap_or_default(),
+                path: path.path.iter().copied().map(saved_hex).collect(),
+                i: path.i,
+            };
+            (e, enemy)
+        })
+        .collect();
+    let index: HashMap<Entity, usize> = enemies
+        .iter()
+        .enumerate()
+        .map(|(i, (e, _))| (*e, i))
+        .collect();
+
+    let world = &*world;
+    let hp = |e: Entity| world.get::<Health>(e).map_or(0., |hp| hp.0);
+    let tiles = map_tiles(world.resource::<Map>())
+        .into_iter()
+        .filter_map(|(tile, te)| {
+            let content = match *world.get::<TileType>(te)? {
+                TileType::Unoccupied => return None,
+                TileType::Terrain(e) => SavedContent::Terrain { hp: hp(e) },
+                TileType::Heart(e) => {
+                    let beat = &world.get::<Heartbeat>(e)?.0;
+                    SavedContent::Heart {
+                        hp: hp(e),
+                        beat: beat.elapsed_secs(),
+                        beat_duration: beat.duration().as_secs_f32(),
+                    }
+                }
+                TileType::CyberNode { meta, e } => {
+                    let cfg = world.get::<PortCfg>(e)?;
+                    let mut inputs: Vec<_> = cfg
+                        .inputs
+                        .iter()
+                        .filter_map(|(offset, pe)| {
+                            Some((saved_hex(*offset), world.get::<PortMeta>(*pe)?.name.clone()))
+                        })
+                        .collect();
+                    // keep the file stable between saves of the same board
+                    inputs.sort();
+
+                    SavedContent::Node {
+                        node: world.get::<NodeKind>(meta)?.clone(),
+                        hp: hp(e),
+                        inputs,
+                        constant: cfg.constant.as_ref().map(|v| SavedVal::new(v, &index)),
+                        storage: world
+                            .get::<StorageSlots>(e)
+                            .map(|s| s.0.iter().map(|v| SavedVal::new(v, &index)).collect()),
+                        plasma: world.get::<PlasmaCounter>(e).map(|c| c.0),
+                    }
+                }
+            };
+
+            Some(SavedTile {
+                tile: saved_hex(tile),
+                content,
+            })
+        })
+        .collect();
+
+    SaveFile {
+        version: SAVE_VERSION,
+        heal: world
+            .get_resource::<HealTimer>()
+            .map_or(0., |t| t.0.elapsed_secs()),
+        heartbeats: world.get_resource::<Heartbeats>().map_or(0, |h| h.0),
+        credits: world.get_resource::<Credits>().map(|c| c.0),
+        waves: world
+            .get_resource::<WaveDirector>()
+            .cloned()
+            .unwrap_or_default(),
+        tiles,
+        enemies: enemies.into_iter().map(|(_, enemy)| enemy).collect(),
+    }
+}
+
+// replaces everything on the board with the saved state
+fn restore(world: &mut World, save: SaveFile) {
+    let common = world.resource::<CommonResources>();
+    let unoccupied = common.unoccupied_mat.clone();
+    let heart_mat = common.heart_mat.clone();
+    let illusion = common.illusion.clone();
+    let by_node = world.resource::<NodeItems>().by_node.clone();
+    let tiles: HashMap<Hex, Entity> = map_tiles(world.resource::<Map>()).into_iter().collect();
+
+    for te in tiles.values() {
+        let occupant = match *world.get::<TileType>(*te).unwrap() {
+            TileType::Unoccupied => None,
+            TileType::Terrain(e) | TileType::Heart(e) | TileType::CyberNode { e, .. } => Some(e),
+        };
+        if let Some(e) = occupant {
+            world.entity_mut(e).despawn_recursive();
+        }
+        *world.get_mut::<TileType>(*te).unwrap() = TileType::Unoccupied;
+        *world.get_mut::<Handle<ColorMaterial>>(*te).unwrap() = unoccupied.clone();
+    }
+
+    let old_enemies: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<TargetableEntity>, With<Dying>)>>()
+        .iter(world)
+        .collect();
+    for e in old_enemies {
+        world.entity_mut(e).despawn_recursive();
+    }
+
+    // reserved up front so values pointing to enemies can be restored
+    let enemies: Vec<Entity> = save
+        .enemies
+        .iter()
+        .map(|_| world.spawn_empty().id())
+        .collect();
+
+    let mut hearts = HashMap::new();
+    for saved in save.tiles {
+        let tile = hex(saved.tile);
+        let Some(te) = tiles.get(&tile).copied() else {
+            warn!("save: tile {:?} is out of bounds, skipping", tile);
+            continue;
+        };
+
+        let (tt, mat) = match saved.content {
+            SavedContent::Terrain { hp } => {
+                let e = world.spawn((Health(hp), HexPos(tile))).id();
+                (TileType::Terrain(e), illusion.clone())
+            }
+            SavedContent::Heart {
+                hp,
+                beat,
+                beat_duration,
+            } => {
+                let mut timer = Timer::from_seconds(beat_duration, TimerMode::Repeating);
+                timer.set_elapsed(Duration::from_secs_f32(beat));
+                let e = world
+                    .spawn(HeartBundle {
+                        beat: Heartbeat(timer),
+                        tile: HexPos(tile),
+                        hp: Health(hp),
+                    })
+                    .id();
+                hearts.insert(tile, e);
+                (TileType::Heart(e), heart_mat.clone())
+            }
+            SavedContent::Node {
+                node,
+                hp,
+                inputs,
+                constant,
+                storage,
+                plasma,
+            } => {
+                let Some(meta) = by_node.get(&node).copied() else {
+                    warn!("save: {:?} is not in the node catalog, skipping", node);
+                    continue;
+                };
+
+                let ports = world.get::<PortMetas>(meta).unwrap().0.clone();
+                let inputs = inputs
+                    .into_iter()
+                    .filter_map(|(offset, name)| {
+                        let pe = ports
+                            .iter()
+                            .copied()
+                            .find(|pe| world.get::<PortMeta>(*pe).unwrap().name == name);
+                        if pe.is_none() {
+                            warn!("save: {:?} has no port {:?}, dropping input", node, name);
+                        }
+                        Some((hex(offset), pe?))
+                    })
+                    .collect();
+
+                let cfg = PortCfg {
+                    inputs,
+                    constant: constant.map(|v| v.val(&enemies)),
+                };
+                let mut e = world.spawn(NodeBundle::placed(meta, cfg, tile, hp));
+                if let Some(storage) = storage {
+                    let slots: Vec<Val> = storage.iter().map(|v| v.val(&enemies)).collect();
+                    match slots.try_into() {
+                        Ok(slots) => {
+                            e.insert(StorageSlots(slots));
+                        }
+                        Err(_) => warn!("save: storage at {:?} has the wrong slot count", tile),
+                    }
+                }
+                if let Some(count) = plasma {
+                    e.insert(PlasmaCounter(count));
+                }
+
+                let e = e.id();
+                let mat = world.get::<Handle<ColorMaterial>>(meta).unwrap().clone();
+                (TileType::CyberNode { meta, e }, mat)
+            }
+        };
+
+        *world.get_mut::<TileType>(te).unwrap() = tt;
+        *world.get_mut::<Handle<ColorMaterial>>(te).unwrap() = mat;
+    }
+
+    let res = world.resource::<EnemyRes>();
+    let archetypes = world.resource::<Archetypes>();
+    let bundles: Vec<_> = save
+        .enemies
+        .into_iter()
+        .map(|saved| {
+            let path: Vec<Hex> = saved.path.into_iter().map(hex).collect();
+            // enemies always walk towards a heart, without one they just keep walking
+            let target = path
+                .last()
+                .and_then(|h| hearts.get(h))
+                .copied()
+                .unwrap_or(Entity::PLACEHOLDER);
+            let pos = Vec2::from_array(saved.pos);
+            let path = PathfindPath { path, i: saved.i };
+            EnemyBundle::new(
+                res,
+                archetypes,
+                pos,
+                target,
+                path,
+                saved.hp,
+                saved.archetype,
+            )
+        })
+        .collect();
+    for (e, bundle) in enemies.into_iter().zip(bundles) {
+        world.entity_mut(e).insert(bundle);
+    }
+
+    let mut heal = Timer::from_seconds(10., TimerMode::Repeating);
+    heal.set_elapsed(Duration::from_secs_f32(save.heal));
+    world.insert_resource(HealTimer(heal));
+    world.insert_resource(Heartbeats(save.heartbeats));
+    world.insert_resource(save.credits.map_or_else(Credits::default, Credits));
+    world.insert_resource(save.waves);
+}
+
+fn save_game(mut cmd: Commands, mut evt: EventReader<SaveGame>) {
+    for SaveGame(path) in evt.read() {
+        let path = path.clone();
+        cmd.add(move |world: &mut World| save_logged(world, &path));
+    }
+}
+
+fn load_game(mut cmd: Commands, mut evt: EventReader<LoadGame>) {
+    for LoadGame(path) in evt.read() {
+        let path = path.clone();
+        cmd.add(move |world: &mut World| match load(world, &path) {
+            Ok(()) => info!("loaded game from {:?}", path),
+            Err(e) => error!("could not load game from {:?}: {}", path, e),
+        });
+    }
+}
+
+// saves right away, save_game does not run outside of the game
+fn autosave(mut cmd: Commands, settings: Res<SaveSettings>) {
+    if let Some(path) = settings.autosave.clone() {
+        cmd.add(move |world: &mut World| save_logged(world, &path));
+    }
+}
+
+fn save_logged(world: &mut World, path: &Path) {
+    match save(world, path) {
+        Ok(()) => info!("saved game to {:?}", path),
+        Err(e) => error!("could not save game to {:?}: {}", path, e),
+    }
+}
+
+pub fn quicksave_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        save.send(SaveGame(QUICKSAVE_PATH.into()));
+    }
+    if keys.just_pressed(KeyCode::F9) {
+        load.send(LoadGame(QUICKSAVE_PATH.into()));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeSet;
+
+use bevy::{
+    ecs::system::SystemState,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use hexx::Hex;
+
+use crate::{
+    debugger::{Debugger, Step},
+    nodes::{CyberState, HexPos, MetaLink, NodeError, NodeKind, PortCfg, PortMeta},
+    registry::{latch_cyber_node, run_cyber_node, NodeRegistry},
+    Map, Tick, TileType,
+};
+
+type NodeQuery = (
+    Entity,
+    &'static CyberState,
+    &'static HexPos,
+    &'static PortCfg,
+    &'static MetaLink,
+);
+
+// heartbeats since the game started, counting the one being evaluated
+#[derive(Resource, Default, Clone, Copy)]
+pub struct Heartbeats(pub u64);
+
+// the order a heartbeat is evaluated in
+#[derive(Debug, Default)]
+pub struct Plan {
+    // every node comes after the nodes it reads from
+    pub order: Vec<Entity>,
+    // nodes that read from themselves through other nodes
+    pub cycles: Vec<Entity>,
+}
+
+// tiles of the nodes that were wired in a loop on the last heartbeat
+#[derive(Resource, Default, Debug)]
+pub struct Loops(pub Vec<Hex>);
+
+// heartbeats that were ticked but not evaluated yet, the debugger can stop one halfway
+#[derive(Resource, Default)]
+pub struct Evaluation {
+    queued: usize,
+    // the heartbeat being evaluated and the next node in its order
+    current: Option<(Plan, usize)>,
+}
+
+impl Evaluation {
+    pub fn in_progress(&self) -> bool {
+        self.queued > 0 || self.current.is_some()
+    }
+}
+
+// evaluates every enabled node once per heartbeat
+pub fn evaluate_heartbeats(
+    world: &mut World,
+    ticks: &mut SystemState<EventReader<Tick>>,
+    nodes: &mut QueryState<NodeQuery>,
+) {
+    let n = ticks.get_mut(world).read().count();
+    let mut eval = world.remove_resource::<Evaluation>().unwrap_or_default();
+    eval.queued += n;
+
+    while world
+        .get_resource::<Debugger>()
+        .map(Debugger::running)
+        .unwrap_or(true)
+    {
+        let (plan, i) = match eval.current.take() {
+            Some(current) => current,
+            None if eval.queued > 0 => {
+                eval.queued -= 1;
+                world.get_resource_or_insert_with(Heartbeats::default).0 += 1;
+                let plan = plan(world, nodes);
+                start_heartbeat(world, &plan);
+                (plan, 0)
+            }
+            None => break,
+        };
+
+        let Some(e) = plan.order.get(i).copied() else {
+            finish_heartbeat(world, &plan);
+            continue;
+        };
+        run_cyber_node(world, e);
+        eval.current = Some((plan, i + 1));
+
+        let evaluated = world
+            .get::<HexPos>(e)
+            .map(|p| p.0)
+            .zip(world.get::<CyberState>(e).cloned());
+        if let (Some((tile, state)), Some(mut debugger)) =
+            (evaluated, world.get_resource_mut::<Debugger>())
+        {
+            debugger.evaluated(tile, &state);
+        }
+    }
+
+    world.insert_resource(eval);
+}
+
+fn start_heartbeat(world: &mut World, plan: &Plan) {
+    let tiles = plan
+        .cycles
+        .iter()
+        .filter_map(|e| world.get::<HexPos>(*e).map(|p| p.0))
+        .collect();
+    world.insert_resource(Loops(tiles));
+
+    for e in plan.cycles.iter() {
+        warn!("node {:?} is wired in a loop", e);
+        if let Some(mut state) = world.get_mut::<CyberState>(*e) {
+            *state = CyberState::Done(Err(NodeError::Cycle));
+        }
+    }
+}
+
+fn finish_heartbeat(world: &mut World, plan: &Plan) {
+    // latched inputs see the values of this heartbeat and are read on the next
+    for e in plan.order.iter().chain(plan.cycles.iter()) {
+        latch_cyber_node(world, *e);
+    }
+
+    if let Some(mut debugger) = world.get_resource_mut::<Debugger>() {
+        if debugger.step == Some(Step::Heartbeat) {
+            debugger.step = None;
+        }
+    }
+}
+
+// sorts the enabled nodes so that every input is evaluated before it is read.
+// ties are broken by tile, so the same map always runs in the same order
+pub fn plan(world: &World, nodes: &mut QueryState<NodeQuery>) -> Plan {
+    let map = world.resource::<Map>();
+    let registry = world.resource::<NodeRegistry>();
+
+    let mut keys = HashMap::default();
+    let mut downstream: HashMap<Entity, Vec<Entity>> = HashMap::default();
+    let mut pending: HashMap<Entity, usize> = HashMap::default();
+
+    let enabled: Vec<_> = nodes
+        .iter(world)
+        .filter(|(_, state, ..)| **state != CyberState::Disabled)
+        .collect();
+    for (e, _, pos, ..) in enabled.iter() {
+        keys.insert(*e, (pos.0.x, pos.0.y, *e));
+        pending.insert(*e, 0);
+    }
+
+    for (e, _, pos, cfg, ml) in enabled.iter() {
+        let Some(kind) = world.get::<NodeKind>(ml.0) else {
+            continue;
+        };
+        let names = registry.inputs(kind);
+
+        for (offset, pe) in cfg.inputs.iter() {
+            // latched ports are read after the heartbeat and don't order it
+            let Some(meta) = world.get::<PortMeta>(*pe) else {
+                continue;
+            };
+            if !names.contains(&meta.name.as_str()) {
+                continue;
+            }
+
+            let Some(tile) = map.storage.get(pos.0 + *offset) else {
+                continue;
+            };
+            let Some(TileType::CyberNode { e: up, .. }) = world.get::<TileType>(*tile) else {
+                continue;
+            };
+            if !keys.contains_key(up) {
+                continue;
+            }
+
+            downstream.entry(*up).or_default().push(*e);
+            *pending.get_mut(e).unwrap() += 1;
+        }
+    }
+
+    let mut ready: BTreeSet<_> = pending
+        .iter()
+        .filter(|(_, n)| **n == 0)
+        .map(|(e, _)| keys[e])
+        .collect();
+    let mut plan = Plan::default();
+    let mut in_cycle = HashSet::default();
+
+    loop {
+        while let Some((.., e)) = ready.pop_first() {
+            plan.order.push(e);
+            for d in downstream.get(&e).into_iter().flatten() {
+                let n = pending.get_mut(d).unwrap();
+                *n -= 1;
+                if *n == 0 {
+                    ready.insert(keys[d]);
+                }
+            }
+        }
+
+        // every node left waits on another one, some of them form loops
+        let mut stuck: Vec<_> = pending
+            .iter()
+            .filter(|(e, n)| **n > 0 && !in_cycle.contains(*e))
+            .map(|(e, _)| keys[e])
+            .collect();
+        if stuck.is_empty() {
+            break;
+        }
+        stuck.sort();
+
+        let stuck_set: HashSet<_> = stuck.iter().map(|(.., e)| *e).collect();
+        let looped: Vec<_> = stuck
+            .iter()
+            .map(|(.., e)| *e)
+            .filter(|e| reaches_itself(*e, &downstream, &stuck_set))
+            .collect();
+        if looped.is_empty() {
+            // can't happen, a node only waits on nodes that are left
+            error!("node plan stalled without a loop");
+            break;
+        }
+
+        // the loop fails as a whole, the nodes it feeds can still run
+        for e in looped.iter() {
+            in_cycle.insert(*e);
+            plan.cycles.push(*e);
+        }
+        for e in looped.iter() {
+            for d in downstream.get(e).into_iter().flatten() {
+                if in_cycle.contains(d) {
+                    continue;
+                }
+                let n = pending.get_mut(d).unwrap();
+                *n -= 1;
+                if *n == 0 {
+                    ready.insert(keys[d]);
+                }
+            }
+        }
+    }
+
+    plan
+}
+
+fn reaches_itself(
+    start: Entity,
+    downstream: &HashMap<Entity, Vec<Entity>>,
+    within: &HashSet<Entity>,
+) -> bool {
+    let mut seen = HashSet::default();
+    let mut open = vec![start];
+    while let Some(e) = open.pop() {
+        for d in downstream.get(&e).into_iter().flatten() {
+            if *d == start {
+                return true;
+            }
+            if within.contains(d) && seen.insert(*d) {
+                open.push(*d);
+            }
+        }
+    }
+    false
+}
@@ -1,6 +1,8 @@
+use crate::catalog::{CatalogChanged, NodeItems};
+use crate::economy::{Credits, Price};
 use crate::nodes::{
-    CyberNodes, Description, ItemMeta, ItemMetaBundle, Name, OutputPort, PortMeta, PortMetas,
-    ValType,
+    Description, IconLabel, ItemMeta, ItemMetaBundle, Name, NodeKind, OutputPort, PortMeta,
+    PortMetas, ValType,
 };
 use crate::{ui::UIRoot, Debug};
 use crate::{Gamestate, ShoppingForTile};
@@ -22,7 +24,7 @@ impl Plugin for ShopPlugin {
                 )
                     .run_if(in_state(Gamestate::Shop)),
             )
-            .add_event::<PickedItem>()
+            .add_systems(Update, fill_slots)
             .add_systems(OnEnter(Gamestate::Shop), show_shop)
             .add_systems(OnExit(Gamestate::Shop), hide_shop);
     }
@@ -43,6 +45,13 @@ struct ItemDescription;
 #[derive(Component)]
 struct ShopTile;
 
+// the item meta offered by a shop tile
+#[derive(Component)]
+struct ShopSlot(Entity);
+
+#[derive(Resource)]
+struct EmptyItem(Entity);
+
 #[derive(Component)]
 struct ShopGrid;
 
@@ -107,630 +116,23 @@ fn spawn_shop(
         ))
         .id();
 
-    let mut items = [
-        ItemMetaBundle::new(
-            "lazor".to_string(),
-            "shoots lazor beam at target".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "the target entity to shoot".to_string(),
-                    ValType::Entity,
-                    false,
-                ))
-                .id()],
-            empty_out,
-            ass.load("nodes/lazor.png"),
-            CyberNodes::Lazor,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "rocket launcher".to_string(),
-            "shoots rockets in the target direction".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "direction".to_string(),
-                    "the direction to shoot in".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id()],
-            empty_out,
-            ass.load("nodes/launcher.png"),
-            CyberNodes::RocketLauncher,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "orbital strike".to_string(),
-            "request an orbital strike at a position that will arrive in the future".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "the target position".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id()],
-            empty_out,
-            ass.load("nodes/orbital.png"),
-            CyberNodes::Orbital,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "plasma cannon".to_string(),
-            "shoot a plasma to the target position. the size of the plasma depends on how many ticks the plasma cannon has been charged".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "the target position".to_string(),
-                    ValType::Vec,
-                    false,
-                )).id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "threshold".to_string(),
-                    "the amount of ticks to collect before firing. default is max: 10".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "fired".to_string(),
-                "power of the shot or 0 if it didnt fire this tick".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/plasma.png"),
-            CyberNodes::Plasma,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "tesla coil".to_string(),
-            "shoot lightning at all targets".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "targets".to_string(),
-                    "list of target entities".to_string(),
-                    ValType::List,
-                    false,
-                )).id(),
-                ],
-            cmd.spawn(PortMeta::new_meta(
-                "shot".to_string(),
-                "number of targets shot".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/shock.png"),
-            CyberNodes::Shock,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "project tile".to_string(),
-            "project an illusory tile at the target position".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "target position".to_string(),
-                    ValType::Vec,
-                    false,
-                )).id(),
-                ],
-            empty_out,
-            ass.load("nodes/project_tile.png"),
-            CyberNodes::Project,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "debug".to_string(),
-            "log all inputs to the console".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "a".to_string(),
-                    "first item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "b".to_string(),
-                    "second item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "c".to_string(),
-                    "third item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "d".to_string(),
-                    "fourth item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "e".to_string(),
-                    "fifth item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-            ],
-            empty_out,
-            ass.load("nodes/debug.png"),
-            CyberNodes::Debug,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "list: construct".to_string(),
-            "construct list out of all inputs, input lists will be flattened".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "a".to_string(),
-                    "first item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "b".to_string(),
-                    "second item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "c".to_string(),
-                    "third item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "d".to_string(),
-                    "fourth item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "e".to_string(),
-                    "fifth item".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "list".to_string(),
-                "1 dimentional list of all inputs".to_string(),
-                ValType::List,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/list.png"),
-            CyberNodes::List,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "entity: closest".to_string(),
-            "returns the closest nearby entity".to_string(),
-            &[],
-            cmd.spawn(PortMeta::new_meta(
-                "closest".to_string(),
-                "the closest nearby entity".to_string(),
-                ValType::Entity,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/closest.png"),
-            CyberNodes::ClosestEntity,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "number: constant".to_string(),
-            "returns a constant number set in the port config".to_string(),
+    let emptyimg = ass.load("nodes/empty.png");
+    let empty = cmd
+        .spawn(ItemMetaBundle::new(
+            "empty slot".to_string(),
+            "wip".to_string(),
             &[],
-            cmd.spawn(PortMeta::new_meta(
-                "constant".to_string(),
-                "the constant value".to_string(),
-                ValType::Number,
-                true,
-            ))
-            .id(),
-            ass.load("nodes/const_number.png"),
-            CyberNodes::ConstantNumber,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "vector: multiply".to_string(),
-            "multilies an arbitrary amount of vectors".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "a".to_string(),
-                    "first vector".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "b".to_string(),
-                    "second vector".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "c".to_string(),
-                    "third vector".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "d".to_string(),
-                    "fourth vector".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "e".to_string(),
-                    "fifth vector".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "vector".to_string(),
-                "a vector like: (ax * bx * cx ..., ay * by...)".to_string(),
-                ValType::Vec,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/vector_mul.png"),
-            CyberNodes::VectorMul,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "number: multiply".to_string(),
-            "multiplies an arbitrary amount of numbers together".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "a".to_string(),
-                    "first number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "b".to_string(),
-                    "second number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "c".to_string(),
-                    "third number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "d".to_string(),
-                    "fourth number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "e".to_string(),
-                    "fifth number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "sum".to_string(),
-                "a * b * c * d * e".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/multiply_number.png"),
-            CyberNodes::NumberMul,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "number: subtract".to_string(),
-            "subtracts an arbitrary amount of numbers in order of the input ports".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "a".to_string(),
-                    "first number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "b".to_string(),
-                    "second number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "c".to_string(),
-                    "third number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "d".to_string(),
-                    "fourth number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "e".to_string(),
-                    "fifth number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "sum".to_string(),
-                "a - b - c - d - e".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/subtract_number.png"),
-            CyberNodes::NumberSub,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "store".to_string(),
-            "unimplemented, sorry :(".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "0".to_string(),
-                    "data slot 0".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "1".to_string(),
-                    "data slot 1".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "2".to_string(),
-                    "data slot 2".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "3".to_string(),
-                    "data slot 3".to_string(),
-                    ValType::Any,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "slot".to_string(),
-                    "determines the slot to output. default is 0".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "data".to_string(),
-                "the stored data from the slot `slot`".to_string(),
-                ValType::Any,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/storage.png"),
-            CyberNodes::Storage,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "vector: create".to_string(),
-            "constructs a vector from 2 numbers".to_string(),
-            &[
-                cmd.spawn(PortMeta::new_meta(
-                    "x".to_string(),
-                    "first number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-                cmd.spawn(PortMeta::new_meta(
-                    "y".to_string(),
-                    "second number".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id(),
-            ],
-            cmd.spawn(PortMeta::new_meta(
-                "vector".to_string(),
-                "the constructed vector".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/vector.png"),
-            CyberNodes::Vector,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "vector: negate".to_string(),
-            "negates a vector".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "vector".to_string(),
-                    "the vector to negate".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "vector".to_string(),
-                "the negated vector".to_string(),
-                ValType::Vec,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/vector_neg.png"),
-            CyberNodes::VectorNeg,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "list: len".to_string(),
-            "returns the length of a list".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "list".to_string(),
-                    "list input".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "length".to_string(),
-                "the number of elements in the list".to_string(),
-                ValType::Vec,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/listlen.png"),
-            CyberNodes::ListLength,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "vector: length".to_string(),
-            "computes the length / magnitude of a vector".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "vector".to_string(),
-                    "the vector to compute".to_string(),
-                    ValType::Vec,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "length".to_string(),
-                "the length of the vector".to_string(),
-                ValType::Number,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/vector_length.png"),
-            CyberNodes::VectorLen,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "entity: nearby".to_string(),
-            "returns all nearby entities as a list of Entity".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "range".to_string(),
-                    "limit to the range. default is max: 10.".to_string(),
-                    ValType::Number,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "entities".to_string(),
-                "the nearby entities".to_string(),
-                ValType::List,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/all_entities.png"),
-            CyberNodes::NearbyEntities,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "entity: direction".to_string(),
-            "returns the direction the target entity is moving towards".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "target entity".to_string(),
-                    ValType::Entity,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "direction".to_string(),
-                "direction of the target".to_string(),
-                ValType::Vec,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/entity_dir.png"),
-            CyberNodes::EntityDirection,
-            &mut mats,
-        ),
-        ItemMetaBundle::new(
-            "entity: position".to_string(),
-            "returns the target entities current position".to_string(),
-            &[cmd
-                .spawn(PortMeta::new_meta(
-                    "target".to_string(),
-                    "target entity".to_string(),
-                    ValType::Entity,
-                    false,
-                ))
-                .id()],
-            cmd.spawn(PortMeta::new_meta(
-                "position".to_string(),
-                "position of target entity".to_string(),
-                ValType::Vec,
-                false,
-            ))
-            .id(),
-            ass.load("nodes/position.png"),
-            CyberNodes::EntityPos,
+            empty_out,
+            emptyimg.clone(),
+            NodeKind("wip".to_string()),
+            Price::default(),
             &mut mats,
-        ),
-    ]
-    .into_iter();
-
-    let emptyimg = ass.load("nodes/empty.png");
-    let empty = ItemMetaBundle::new(
-        "empty slot".to_string(),
-        "wip".to_string(),
-        &[],
-        empty_out,
-        emptyimg.clone(),
-        CyberNodes::WIP,
-        &mut mats,
-    );
+        ))
+        .id();
+    cmd.insert_resource(EmptyItem(empty));
 
+    // items are filled in from the node catalog once it is loaded
     let storage = HexagonalMap::new(Hex::ZERO, 5, |_| {
-        let item = items.next().unwrap_or(empty.clone());
-        let tex = item.tex.clone();
         cmd.spawn((
             NodeBundle {
                 style: Style {
@@ -740,7 +142,7 @@ fn spawn_shop(
                 ..default()
             },
             ShopTile,
-            item,
+            ShopSlot(empty),
         ))
         .with_children(|node| {
             node.spawn((ImageBundle {
@@ -749,9 +151,16 @@ fn spawn_shop(
                     height: Val::Auto,
                     ..default()
                 },
-                image: UiImage::new(tex),
+                image: UiImage::new(emptyimg.clone()),
                 ..default()
             },));
+            // the icon label of the item, over the image
+            node.spawn(
+                TextBundle::from_section("", TextStyle::default()).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+            );
         })
         .id()
     });
@@ -882,6 +291,46 @@ fn spawn_shop(
     cmd.insert_resource(ShopUINode(e));
 }
 
+fn fill_slots(
+    mut changed: EventReader<CatalogChanged>,
+    items: Res<NodeItems>,
+    empty: Res<EmptyItem>,
+    grid: Query<&ShopItems>,
+    mut slots: Query<(&mut ShopSlot, &Children)>,
+    mut imgs: Query<&mut UiImage>,
+    mut texts: Query<&mut Text>,
+    texs: Query<&Handle<Image>, With<ItemMeta>>,
+    icons: Query<&IconLabel>,
+) {
+    if changed.read().last().is_none() {
+        return;
+    }
+
+    let Ok(grid) = grid.get_single() else {
+        return;
+    };
+
+    let mut offered = items.order.iter().copied();
+    for (_, tile) in grid.tiles() {
+        let item = offered.next().unwrap_or(empty.0);
+        let (mut slot, children) = slots.get_mut(tile).unwrap();
+        slot.0 = item;
+
+        let Ok(tex) = texs.get(item) else {
+            continue;
+        };
+        let label = icons.get(item).map_or("", |l| l.0.as_str());
+        for child in children.iter() {
+            if let Ok(mut img) = imgs.get_mut(*child) {
+                img.texture = tex.clone();
+            }
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.to_string();
+            }
+        }
+    }
+}
+
 fn grid_selection(
     mut shop: Query<(
         &ShopLayout,
@@ -890,6 +339,7 @@ fn grid_selection(
         &RelativeCursorPosition,
         &Node,
     )>,
+    slots: Query<&ShopSlot>,
     mut dbg: ResMut<Debug>,
 ) {
     for (layout, items, mut selection, rel, node) in shop.iter_mut() {
@@ -914,7 +364,7 @@ fn grid_selection(
         if !items.0.bounds().is_in_bounds(tile) {
             return;
         };
-        selection.node = Some(*items.0.get(tile).unwrap());
+        selection.node = Some(slots.get(*items.0.get(tile).unwrap()).unwrap().0);
         selection.tile = Some(tile);
     }
 }
@@ -922,20 +372,28 @@ fn grid_selection(
 fn update_description(
     shop: Query<&ShopSelection>,
     mut desc: Query<&mut Text, With<ItemDescription>>,
-    items: Query<(&Name, &Description, &PortMetas, &OutputPort), With<ItemMeta>>,
+    items: Query<(&Name, &Description, &PortMetas, &OutputPort, &Price), With<ItemMeta>>,
     ports: Query<&PortMeta>,
+    credits: Res<Credits>,
 ) {
     for selection in shop.iter() {
         let Some(node) = selection.node else { continue };
         let mut text = desc.single_mut();
-        let (name, desc, pms, opm) = items.get(node).unwrap();
+        let (name, desc, pms, opm, price) = items.get(node).unwrap();
 
         let style = TextStyle::default();
+        let afford = if credits.can_afford(*price) {
+            ""
+        } else {
+            " (can't afford)"
+        };
         text.sections = vec![
             TextSection::new(&name.0, style.clone()),
             TextSection::new("\n", style.clone()),
             TextSection::new(&desc.0, style.clone()),
             TextSection::new("\n\n", style.clone()),
+            TextSection::new(format!("price: {}{}", price.0, afford), style.clone()),
+            TextSection::new("\n\n", style.clone()),
             TextSection::new("input ports\n", style.clone()),
         ];
 
@@ -984,6 +442,8 @@ fn pick_grid(
     mouse: Res<ButtonInput<MouseButton>>,
     shoppingfor: Res<ShoppingForTile>,
     selection: Query<&ShopSelection>,
+    prices: Query<&Price>,
+    credits: Res<Credits>,
     mut evt: EventWriter<PickedItem>,
 ) {
     if !mouse.just_pressed(MouseButton::Left) {
@@ -991,6 +451,11 @@ fn pick_grid(
     }
     for s in selection.iter() {
         let Some(node) = s.node else { continue };
+        // stay in the shop so something cheaper can be picked
+        if !credits.can_afford(prices.get(node).copied().unwrap_or_default()) {
+            info!("can't afford item");
+            continue;
+        }
         evt.send(PickedItem {
             item: node,
             tile: shoppingfor.0.expect("shop open without shoppingfor"),
@@ -0,0 +1,1838 @@
+use bevy::{math::vec2, prelude::*};
+use hexx::Hex;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    animation::{Animation, Clip},
+    blueprint::Blueprint,
+    catalog::{NodeCatalog, NodeItems},
+    debugger::{Breakpoint, Debugger, Step},
+    economy::{Credits, KILL_REWARD},
+    enemy::{
+        self, Archetype, ArchetypeDef, Archetypes, Bolt, Dying, EnemyActivity, EnemyBundle,
+        EnemyKilled, EnemyRes, PathfindPath, Ranged, Speed,
+    },
+    headless::{Sim, SimReport},
+    nodes::{
+        CyberState, DamageKind, DisplayLabel, Health, HexPos, IconLabel, NodeError, NodeKind,
+        PortCfg, Shield, StorageSlots, TargetableEntity, Val, ValType, WiringError, WiringErrors,
+        NODE_HP,
+    },
+    overlay::overlay_text,
+    pathing::{FlowField, PathCosts},
+    scheduler::Loops,
+    waves::{EnemyGroup, WaveDef, WaveDirector, WaveSchedule},
+    Map, Tick, TileClicked, TileType,
+};
+
+// the tests bring their own catalog, waves and path costs, tuning the game can't break them
+const FIXTURES: &str = "tests/fixtures";
+
+// nodes are laid out in a line next to the heart so they are all connected
+fn at(x: i32) -> Hex {
+    Hex::new(x, 0)
+}
+
+fn sim() -> Sim {
+    Sim::with_assets(App::new(), 0, FIXTURES)
+}
+
+impl Sim {
+    fn heart(&self) -> Entity {
+        let world = self.app.world();
+        let tile = world
+            .resource::<Map>()
+            .storage
+            .get(Hex::ZERO)
+            .copied()
+            .unwrap();
+        match world.get::<TileType>(tile) {
+            Some(TileType::Heart(e)) => *e,
+            t => panic!("no heart in the middle: {:?}", t),
+        }
+    }
+
+    // sends a heartbeat and steps until the nodes are done evaluating
+    fn tick(&mut self) {
+        let heart = self.heart();
+        self.app.world_mut().send_event(Tick(heart));
+        self.settle();
+    }
+
+    fn number(&mut self, tile: Hex, n: f32) {
+        self.place("constant_number", tile);
+        self.set_constant(tile, Val::Number(n));
+    }
+
+    fn vector(&mut self, tile: Hex, v: Vec2, x: Hex, y: Hex) {
+        self.number(x, v.x);
+        self.number(y, v.y);
+        self.place("vector", tile);
+        self.connect(tile, "x", x);
+        self.connect(tile, "y", y);
+    }
+
+    fn loops(&self) -> Vec<Hex> {
+        self.app.world().resource::<Loops>().0.clone()
+    }
+
+    fn disconnect(&mut self, tile: Hex, from: Hex) {
+        let e = self.node_at(tile).unwrap();
+        let mut cfg = self.app.world_mut().get_mut::<PortCfg>(e).unwrap();
+        cfg.inputs.remove(&(from - tile));
+    }
+
+    fn state(&self, tile: Hex) -> CyberState {
+        let e = self.node_at(tile).expect("no node on tile");
+        self.app.world().get::<CyberState>(e).unwrap().clone()
+    }
+
+    fn credits(&self) -> u32 {
+        self.app.world().resource::<Credits>().0
+    }
+
+    fn done(&self, tile: Hex) -> Result<Val, NodeError> {
+        match self.state(tile) {
+            CyberState::Done(res) => res,
+            s => panic!("node at {:?} is not done: {:?}", tile, s),
+        }
+    }
+
+    fn spawn_enemy(&mut self, tile: Hex, hp: f32) -> Entity {
+        let pos = self
+            .app
+            .world()
+            .resource::<Map>()
+            .layout
+            .hex_to_world_pos(tile);
+        self.app
+            .world_mut()
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(pos.extend(1.))),
+                TargetableEntity,
+                Health(hp),
+            ))
+            .id()
+    }
+
+    // a walking enemy of `archetype` with 100 hp, on its way to the heart
+    fn spawn_walker(&mut self, pos: Hex, path: PathfindPath, archetype: &str) -> Entity {
+        let heart = self.heart();
+        let world = self.app.world_mut();
+        let pos = world.resource::<Map>().layout.hex_to_world_pos(pos);
+        let bundle = EnemyBundle::new(
+            world.resource::<EnemyRes>(),
+            world.resource::<Archetypes>(),
+            pos,
+            heart,
+            path,
+            100.,
+            Archetype::new(archetype),
+        );
+        world.spawn(bundle).id()
+    }
+
+    fn slots(&self, tile: Hex) -> [Val; 4] {
+        let e = self.node_at(tile).unwrap();
+        self.app.world().get::<StorageSlots>(e).unwrap().0.clone()
+    }
+
+    fn wiring(&mut self, tile: Hex) -> Vec<WiringError> {
+        // checked once per update
+        self.step();
+        let e = self.node_at(tile).unwrap();
+        self.app.world().get::<WiringErrors>(e).unwrap().0.clone()
+    }
+
+    fn hp(&self, e: Entity) -> f32 {
+        self.app.world().get::<Health>(e).unwrap().0
+    }
+}
+
+fn assert_vec(v: Result<Val, NodeError>, expected: Vec2) {
+    match v {
+        Ok(Val::Vec(v)) => assert!(
+            v.distance(expected) < 1e-4,
+            "expected {:?}, got {:?}",
+            expected,
+            v
+        ),
+        v => panic!("expected a vector, got {:?}", v),
+    }
+}
+
+#[test]
+fn disconnected_nodes_stay_disabled() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.number(at(3), 2.);
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(1.)));
+    assert_eq!(sim.state(at(3)), CyberState::Disabled);
+}
+
+#[test]
+fn constant_without_value_errors() {
+    let mut sim = sim();
+    sim.place("constant_number", at(1));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Err(NodeError::NoConstant));
+}
+
+#[test]
+fn errored_input_fails_downstream() {
+    let mut sim = sim();
+    sim.place("constant_number", at(1));
+    sim.place("vector_neg", at(2));
+    sim.connect(at(2), "vector", at(1));
+    sim.place("vector_len", at(3));
+    sim.connect(at(3), "vector", at(2));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Err(NodeError::NoConstant));
+    let upstream = |port: &str| {
+        Err(NodeError::Upstream {
+            port: port.to_string(),
+            at: at(1),
+        })
+    };
+    assert_eq!(sim.done(at(2)), upstream("vector"));
+    // the failure is reported where it started
+    assert_eq!(sim.done(at(3)), upstream("vector"));
+}
+
+#[test]
+fn list_skips_errored_inputs() {
+    let mut sim = sim();
+    sim.place("constant_number", at(1));
+    sim.number(at(2), 2.);
+    sim.place("list", at(3));
+    sim.connect(at(3), "a", at(1));
+    sim.connect(at(3), "b", at(2));
+    sim.tick();
+
+    assert_eq!(sim.done(at(3)), Ok(Val::List(vec![Val::Number(2.)])));
+}
+
+#[test]
+fn list_flattens_inputs() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.number(at(2), 2.);
+    sim.place("list", at(3));
+    sim.connect(at(3), "a", at(1));
+    sim.connect(at(3), "b", at(2));
+    sim.place("debug", at(4));
+    sim.place("list", at(5));
+    sim.connect(at(5), "a", at(3));
+    sim.connect(at(5), "b", at(4));
+    sim.connect(at(5), "c", at(1));
+    sim.tick();
+
+    let n = |n| Val::Number(n);
+    assert_eq!(sim.done(at(3)), Ok(Val::List(vec![n(1.), n(2.)])));
+    // nested lists are flattened and the empty debug output is skipped
+    assert_eq!(sim.done(at(5)), Ok(Val::List(vec![n(1.), n(2.), n(1.)])));
+}
+
+#[test]
+fn list_len_counts_elements() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.number(at(2), 2.);
+    sim.place("list", at(3));
+    sim.connect(at(3), "a", at(1));
+    sim.connect(at(3), "c", at(2));
+    sim.place("list_len", at(4));
+    sim.connect(at(4), "list", at(3));
+    sim.tick();
+
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(2.)));
+}
+
+#[test]
+fn list_len_of_non_list_errors() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.place("list_len", at(2));
+    sim.connect(at(2), "list", at(1));
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(2)),
+        Err(NodeError::WrongType {
+            port: "list".to_string(),
+            expected: ValType::List,
+            got: ValType::Number,
+        })
+    );
+}
+
+#[test]
+fn number_sub_folds_in_port_order() {
+    let mut sim = sim();
+    sim.number(at(1), 10.);
+    sim.number(at(2), 3.);
+    sim.number(at(3), 2.);
+    sim.place("number_sub", at(4));
+    // wired in reverse, the port names decide the order
+    sim.connect(at(4), "e", at(3));
+    sim.connect(at(4), "c", at(2));
+    sim.connect(at(4), "a", at(1));
+    sim.tick();
+
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(5.)));
+}
+
+#[test]
+fn number_sub_starts_at_first_connected_port() {
+    let mut sim = sim();
+    sim.number(at(1), 10.);
+    sim.number(at(2), 3.);
+    sim.place("number_sub", at(3));
+    sim.connect(at(3), "b", at(1));
+    sim.connect(at(3), "d", at(2));
+    sim.tick();
+
+    assert_eq!(sim.done(at(3)), Ok(Val::Number(7.)));
+}
+
+#[test]
+fn number_ops_without_inputs_are_zero() {
+    let mut sim = sim();
+    sim.place("number_sub", at(1));
+    sim.place("number_mul", at(2));
+    sim.place("vector_mul", at(3));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(0.)));
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(0.)));
+    assert_eq!(sim.done(at(3)), Ok(Val::Vec(Vec2::ZERO)));
+}
+
+#[test]
+fn number_mul_multiplies_and_skips_other_types() {
+    let mut sim = sim();
+    sim.number(at(1), 2.);
+    sim.number(at(2), 3.);
+    sim.number(at(3), 4.);
+    sim.place("debug", at(4));
+    sim.place("number_mul", at(5));
+    sim.connect(at(5), "a", at(1));
+    sim.connect(at(5), "b", at(2));
+    sim.connect(at(5), "c", at(4));
+    sim.connect(at(5), "d", at(3));
+    sim.tick();
+
+    assert_eq!(sim.done(at(5)), Ok(Val::Number(24.)));
+}
+
+#[test]
+fn vector_mul_is_componentwise() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(2., 3.), at(1), at(2));
+    sim.vector(at(6), vec2(4., -5.), at(4), at(5));
+    sim.place("vector_mul", at(7));
+    sim.connect(at(7), "a", at(3));
+    sim.connect(at(7), "b", at(6));
+    sim.tick();
+
+    assert_vec(sim.done(at(3)), vec2(2., 3.));
+    assert_vec(sim.done(at(7)), vec2(8., -15.));
+}
+
+#[test]
+fn vector_needs_both_components() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.place("vector", at(2));
+    sim.connect(at(2), "x", at(1));
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(2)),
+        Err(NodeError::NotConfigured("y".to_string()))
+    );
+}
+
+#[test]
+fn vector_neg_and_len() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(3., -4.), at(1), at(2));
+    sim.place("vector_neg", at(4));
+    sim.connect(at(4), "vector", at(3));
+    sim.place("vector_len", at(5));
+    sim.connect(at(5), "vector", at(4));
+    sim.tick();
+
+    assert_vec(sim.done(at(4)), vec2(-3., 4.));
+    assert_eq!(sim.done(at(5)), Ok(Val::Number(5.)));
+}
+
+#[test]
+fn number_add_min_max() {
+    let mut sim = sim();
+    sim.number(at(1), 4.);
+    sim.number(at(2), -2.);
+    sim.number(at(3), 7.);
+    for (node, tile) in [
+        ("number_add", at(4)),
+        ("number_min", at(5)),
+        ("number_max", at(6)),
+    ] {
+        sim.place(node, tile);
+        sim.connect(tile, "a", at(1));
+        sim.connect(tile, "b", at(2));
+        sim.connect(tile, "d", at(3));
+    }
+    sim.tick();
+
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(9.)));
+    assert_eq!(sim.done(at(5)), Ok(Val::Number(-2.)));
+    assert_eq!(sim.done(at(6)), Ok(Val::Number(7.)));
+}
+
+#[test]
+fn number_div_by_zero_errors() {
+    let mut sim = sim();
+    sim.number(at(1), 12.);
+    sim.number(at(2), 3.);
+    sim.number(at(3), 0.);
+    sim.place("number_div", at(4));
+    sim.connect(at(4), "a", at(1));
+    sim.connect(at(4), "b", at(2));
+    sim.tick();
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(4.)));
+
+    sim.connect(at(4), "c", at(3));
+    sim.tick();
+    assert_eq!(
+        sim.done(at(4)),
+        Err(NodeError::OutOfRange {
+            port: "c".to_string(),
+            value: 0.,
+        })
+    );
+}
+
+#[test]
+fn vector_add_sub_dot() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(2., 3.), at(1), at(2));
+    sim.vector(at(6), vec2(4., -5.), at(4), at(5));
+    for (node, tile) in [
+        ("vector_add", at(7)),
+        ("vector_sub", at(8)),
+        ("vector_dot", at(9)),
+    ] {
+        sim.place(node, tile);
+        sim.connect(tile, "a", at(3));
+        sim.connect(tile, "b", at(6));
+    }
+    sim.tick();
+
+    assert_vec(sim.done(at(7)), vec2(6., -2.));
+    assert_vec(sim.done(at(8)), vec2(-2., 8.));
+    assert_eq!(sim.done(at(9)), Ok(Val::Number(-7.)));
+}
+
+#[test]
+fn vector_normalize_and_rotate() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(0., 5.), at(1), at(2));
+    sim.place("vector_normalize", at(4));
+    sim.connect(at(4), "vector", at(3));
+    sim.number(at(5), 90.);
+    sim.place("vector_rotate", at(6));
+    sim.connect(at(6), "vector", at(4));
+    sim.connect(at(6), "angle", at(5));
+    sim.tick();
+
+    assert_vec(sim.done(at(4)), vec2(0., 1.));
+    assert_vec(sim.done(at(6)), vec2(-1., 0.));
+}
+
+#[test]
+fn list_sum_adds_numbers() {
+    let mut sim = sim();
+    sim.number(at(1), 2.);
+    sim.number(at(2), 5.);
+    sim.vector(at(5), vec2(1., 1.), at(3), at(4));
+    sim.place("list", at(6));
+    sim.connect(at(6), "a", at(1));
+    sim.connect(at(6), "b", at(2));
+    sim.connect(at(6), "c", at(5));
+    sim.place("list_sum", at(7));
+    sim.connect(at(7), "list", at(6));
+    sim.tick();
+
+    assert_eq!(sim.done(at(7)), Ok(Val::Number(7.)));
+}
+
+#[test]
+fn comparisons_and_logic() {
+    let mut sim = sim();
+    sim.number(at(1), 2.);
+    sim.number(at(2), 2.00001);
+    for (node, tile) in [
+        ("number_less", at(3)),
+        ("number_greater", at(4)),
+        ("number_equal", at(5)),
+    ] {
+        sim.place(node, tile);
+        sim.connect(tile, "a", at(1));
+        sim.connect(tile, "b", at(2));
+    }
+    for (node, tile) in [("bool_and", at(6)), ("bool_or", at(7)), ("bool_xor", at(8))] {
+        sim.place(node, tile);
+        sim.connect(tile, "a", at(3));
+        sim.connect(tile, "b", at(5));
+    }
+    sim.place("bool_not", at(9));
+    sim.connect(at(9), "bool", at(4));
+    sim.tick();
+
+    assert_eq!(sim.done(at(3)), Ok(Val::Bool(true)));
+    assert_eq!(sim.done(at(4)), Ok(Val::Bool(false)));
+    assert_eq!(sim.done(at(5)), Ok(Val::Bool(true)));
+    assert_eq!(sim.done(at(6)), Ok(Val::Bool(true)));
+    assert_eq!(sim.done(at(7)), Ok(Val::Bool(true)));
+    assert_eq!(sim.done(at(8)), Ok(Val::Bool(false)));
+    assert_eq!(sim.done(at(9)), Ok(Val::Bool(true)));
+}
+
+#[test]
+fn select_only_needs_the_picked_input() {
+    let mut sim = sim();
+    sim.place("constant_bool", at(1));
+    sim.set_constant(at(1), Val::Bool(true));
+    sim.number(at(2), 3.);
+    // errors, but is not picked
+    sim.place("constant_number", at(3));
+    sim.place("select", at(4));
+    sim.connect(at(4), "condition", at(1));
+    sim.connect(at(4), "then", at(2));
+    sim.connect(at(4), "else", at(3));
+    sim.tick();
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(3.)));
+
+    sim.set_constant(at(1), Val::Bool(false));
+    sim.tick();
+    assert_eq!(
+        sim.done(at(4)),
+        Err(NodeError::Upstream {
+            port: "else".to_string(),
+            at: at(3),
+        })
+    );
+}
+
+#[test]
+fn text_format_and_display() {
+    let mut sim = sim();
+    sim.place("constant_text", at(1));
+    sim.set_constant(at(1), Val::Text("hp {} at {}!".to_string()));
+    sim.number(at(2), 2.5);
+    sim.vector(at(5), vec2(1., -2.), at(3), at(4));
+    sim.place("text_format", at(6));
+    sim.connect(at(6), "format", at(1));
+    sim.connect(at(6), "a", at(2));
+    sim.connect(at(6), "b", at(5));
+    sim.place("text_concat", at(7));
+    sim.connect(at(7), "a", at(2));
+    sim.connect(at(7), "c", at(1));
+    sim.place("display", at(8));
+    sim.connect(at(8), "text", at(6));
+    sim.tick();
+
+    let formatted = "hp 2.5 at (1, -2)!".to_string();
+    assert_eq!(sim.done(at(6)), Ok(Val::Text(formatted.clone())));
+    assert_eq!(
+        sim.done(at(7)),
+        Ok(Val::Text("2.5hp {} at {}!".to_string()))
+    );
+
+    let display = sim.node_at(at(8)).unwrap();
+    let labels: Vec<_> = sim
+        .app
+        .world_mut()
+        .query::<(&DisplayLabel, &Text)>()
+        .iter(sim.app.world())
+        .filter(|(l, _)| l.0 == display)
+        .map(|(_, t)| t.sections[0].value.clone())
+        .collect();
+    assert_eq!(labels, vec![formatted]);
+}
+
+#[test]
+fn overlay_labels_show_values() {
+    let mut sim = sim();
+    sim.number(at(1), 1.5);
+    sim.place("list", at(2));
+    sim.connect(at(2), "a", at(1));
+    sim.connect(at(2), "b", at(1));
+    sim.place("constant_number", at(3));
+    sim.tick();
+
+    assert_eq!(overlay_text(&sim.state(at(1))), "1.5");
+    assert_eq!(overlay_text(&sim.state(at(2))), "[2]");
+    assert_eq!(overlay_text(&sim.state(at(3))), "err");
+}
+
+#[test]
+fn plasma_fires_when_threshold_is_reached() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(5., 0.), at(1), at(2));
+    sim.number(at(4), 3.);
+    sim.place("plasma", at(5));
+    sim.connect(at(5), "target", at(3));
+    sim.connect(at(5), "threshold", at(4));
+
+    let mut fired = Vec::new();
+    for _ in 0..7 {
+        sim.tick();
+        fired.push(sim.done(at(5)));
+    }
+
+    let n = |n| Ok(Val::Number(n));
+    assert_eq!(fired, vec![n(0.), n(0.), n(3.), n(0.), n(0.), n(3.), n(0.)]);
+}
+
+#[test]
+fn plasma_without_threshold_fires_every_tick() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(5., 0.), at(1), at(2));
+    sim.place("plasma", at(4));
+    sim.connect(at(4), "target", at(3));
+
+    for _ in 0..3 {
+        sim.tick();
+        assert_eq!(sim.done(at(4)), Ok(Val::Number(1.)));
+    }
+}
+
+#[test]
+fn closest_entity_range() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    let far = sim.spawn_enemy(Hex::new(10, 0), 100.);
+    sim.tick();
+    assert_eq!(sim.done(at(1)), Err(NodeError::NoTarget));
+
+    let near = sim.spawn_enemy(Hex::new(9, 0), 100.);
+    sim.tick();
+    assert_eq!(sim.done(at(1)), Ok(Val::Entity(near)));
+    assert_ne!(far, near);
+}
+
+#[test]
+fn nearby_entities_range() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.tick();
+    assert_eq!(sim.done(at(1)), Ok(Val::List(vec![])));
+
+    let a = sim.spawn_enemy(Hex::new(1, 8), 100.);
+    sim.spawn_enemy(Hex::new(-8, 0), 100.);
+    let b = sim.spawn_enemy(Hex::new(3, 0), 100.);
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(1)),
+        Ok(Val::List(vec![Val::Entity(a), Val::Entity(b)]))
+    );
+}
+
+#[test]
+fn entity_pos_is_in_hex_coordinates() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("entity_pos", at(2));
+    sim.connect(at(2), "target", at(1));
+    sim.spawn_enemy(Hex::new(4, -2), 100.);
+    sim.tick();
+
+    assert_vec(sim.done(at(2)), vec2(4., -2.));
+}
+
+#[test]
+fn entity_direction_points_to_next_path_tile() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("entity_direction", at(2));
+    sim.connect(at(2), "target", at(1));
+    let e = sim.spawn_enemy(Hex::new(4, 0), 100.);
+    sim.app.world_mut().entity_mut(e).insert(PathfindPath {
+        path: vec![Hex::new(5, 0), Hex::new(4, 0), Hex::new(3, 0)],
+        i: 2,
+    });
+    sim.tick();
+
+    let layout = &sim.app.world().resource::<Map>().layout;
+    let expected = (layout.hex_to_world_pos(Hex::new(3, 0))
+        - layout.hex_to_world_pos(Hex::new(4, 0)))
+    .normalize();
+    assert_vec(sim.done(at(2)), expected);
+}
+
+#[test]
+fn entity_direction_at_path_end_is_zero() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("entity_direction", at(2));
+    sim.connect(at(2), "target", at(1));
+    let e = sim.spawn_enemy(Hex::new(4, 0), 100.);
+    sim.app.world_mut().entity_mut(e).insert(PathfindPath {
+        path: vec![Hex::new(5, 0), Hex::new(4, 0)],
+        i: 2,
+    });
+    sim.tick();
+
+    assert_vec(sim.done(at(2)), Vec2::ZERO);
+}
+
+#[test]
+fn entity_health_and_distance() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("entity_health", at(2));
+    sim.connect(at(2), "target", at(1));
+    sim.place("entity_distance", at(3));
+    sim.connect(at(3), "target", at(1));
+    sim.spawn_enemy(Hex::new(6, 0), 40.);
+    sim.tick();
+
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(40.)));
+    match sim.done(at(3)) {
+        Ok(Val::Number(d)) => assert!((d - 3.).abs() < 1e-4, "distance {}", d),
+        v => panic!("expected a distance, got {:?}", v),
+    }
+}
+
+#[test]
+fn entity_predict_follows_the_path() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("entity_speed", at(2));
+    sim.connect(at(2), "target", at(1));
+    sim.place("entity_predict", at(3));
+    sim.connect(at(3), "target", at(1));
+    let e = sim.spawn_enemy(Hex::new(5, 0), 100.);
+    sim.app.world_mut().entity_mut(e).insert((
+        PathfindPath {
+            path: vec![Hex::new(4, 0), Hex::new(3, 0), Hex::new(2, 0)],
+            i: 0,
+        },
+        EnemyActivity::Pathfinding,
+    ));
+    // the time it takes to walk one and a half tiles
+    let tile = {
+        let layout = &sim.app.world().resource::<Map>().layout;
+        layout
+            .hex_to_world_pos(Hex::new(1, 0))
+            .distance(layout.hex_to_world_pos(Hex::ZERO))
+    };
+    sim.number(at(4), 1.5 * tile / enemy::SPEED);
+    sim.connect(at(3), "seconds", at(4));
+    sim.tick();
+
+    match sim.done(at(2)) {
+        Ok(Val::Number(s)) => assert!((s - enemy::SPEED / tile).abs() < 1e-4),
+        v => panic!("expected a speed, got {:?}", v),
+    }
+    // the enemy moved a little while settling
+    match sim.done(at(3)) {
+        Ok(Val::Vec(v)) => assert!(v.distance(vec2(3.5, 0.)) < 0.1, "predicted {:?}", v),
+        v => panic!("expected a position, got {:?}", v),
+    }
+}
+
+#[test]
+fn world_sensors() {
+    let mut sim = sim();
+    sim.place("heart_health", at(1));
+    sim.place("tick_counter", at(2));
+    sim.place("self_position", Hex::new(1, 1));
+    sim.place("enemy_count", at(3));
+    sim.spawn_enemy(Hex::new(6, 0), 10.);
+    sim.spawn_enemy(Hex::new(-6, 0), 10.);
+    sim.tick();
+
+    let hp = sim.hp(sim.heart());
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(hp)));
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(1.)));
+    assert_vec(sim.done(Hex::new(1, 1)), vec2(1., 1.));
+    // the heartbeat may spawn enemies, but only after the nodes ran
+    assert_eq!(sim.done(at(3)), Ok(Val::Number(2.)));
+
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(2.)));
+}
+
+#[test]
+fn random_is_seeded_per_run() {
+    let roll = |seed| {
+        let mut sim = Sim::with_assets(App::new(), seed, FIXTURES);
+        sim.number(at(1), 5.);
+        sim.number(at(2), 10.);
+        sim.place("random", at(3));
+        sim.connect(at(3), "min", at(1));
+        sim.connect(at(3), "max", at(2));
+        sim.tick();
+        sim.done(at(3))
+    };
+
+    let a = roll(7);
+    assert_eq!(a, roll(7));
+    match a {
+        Ok(Val::Number(n)) => assert!((5. ..10.).contains(&n), "rolled {}", n),
+        v => panic!("expected a number, got {:?}", v),
+    }
+}
+
+#[test]
+fn lazor_damages_target() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("lazor", at(2));
+    sim.connect(at(2), "target", at(1));
+    let e = sim.spawn_enemy(Hex::new(3, 0), 100.);
+    sim.tick();
+
+    assert_eq!(sim.done(at(2)), Ok(Val::Empty));
+    assert_eq!(sim.hp(e), 85.);
+}
+
+#[test]
+fn disabled_lazor_skips_firing() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("lazor", at(2));
+    sim.connect(at(2), "target", at(1));
+    sim.place("constant_bool", at(3));
+    sim.set_constant(at(3), Val::Bool(false));
+    sim.connect(at(2), "enable", at(3));
+    let e = sim.spawn_enemy(Hex::new(4, 0), 100.);
+    sim.tick();
+
+    assert_eq!(sim.state(at(2)), CyberState::Skipped);
+    assert_eq!(sim.hp(e), 100.);
+
+    sim.set_constant(at(3), Val::Bool(true));
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Empty));
+    assert_eq!(sim.hp(e), 85.);
+}
+
+#[test]
+fn skipped_nodes_skip_readers_but_not_lists() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(5., 0.), at(1), at(2));
+    sim.place("constant_bool", at(4));
+    sim.set_constant(at(4), Val::Bool(false));
+    sim.place("plasma", at(5));
+    sim.connect(at(5), "target", at(3));
+    sim.connect(at(5), "enable", at(4));
+    sim.place("number_abs", at(6));
+    sim.connect(at(6), "number", at(5));
+    sim.number(at(7), 2.);
+    sim.place("list", at(8));
+    sim.connect(at(8), "a", at(5));
+    sim.connect(at(8), "b", at(7));
+    sim.tick();
+
+    assert_eq!(sim.state(at(5)), CyberState::Skipped);
+    assert_eq!(sim.state(at(6)), CyberState::Skipped);
+    assert_eq!(sim.done(at(8)), Ok(Val::List(vec![Val::Number(2.)])));
+}
+
+#[test]
+fn shock_damages_all_entities_in_list() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.number(at(2), 1.);
+    sim.place("list", at(3));
+    sim.connect(at(3), "a", at(1));
+    sim.connect(at(3), "b", at(2));
+    sim.place("shock", at(4));
+    sim.connect(at(4), "targets", at(3));
+    let a = sim.spawn_enemy(Hex::new(3, 1), 100.);
+    let b = sim.spawn_enemy(Hex::new(-2, 0), 50.);
+    sim.tick();
+
+    // the number in the list is skipped
+    assert_eq!(sim.done(at(4)), Ok(Val::Number(2.)));
+    assert_eq!(sim.hp(a), 95.);
+    assert_eq!(sim.hp(b), 45.);
+}
+
+#[test]
+fn list_sort_filter_and_slice_entities() {
+    let mut sim = sim();
+    let a = sim.spawn_enemy(Hex::new(1, 0), 80.);
+    let b = sim.spawn_enemy(Hex::new(-5, 0), 30.);
+    let c = sim.spawn_enemy(Hex::new(4, 0), 50.);
+    sim.place("nearby_entities", at(1));
+    sim.place("list_sort_distance", at(2));
+    sim.connect(at(2), "list", at(1));
+    sim.place("list_sort_health", at(3));
+    sim.connect(at(3), "list", at(1));
+    sim.place("list_filter_health", at(4));
+    sim.number(at(5), 40.);
+    sim.connect(at(4), "list", at(3));
+    sim.connect(at(4), "min", at(5));
+    sim.place("list_first", at(6));
+    sim.connect(at(6), "list", at(2));
+    sim.place("list_last", at(7));
+    sim.number(at(8), 2.);
+    sim.connect(at(7), "list", at(3));
+    sim.connect(at(7), "count", at(8));
+    sim.place("list_index", at(9));
+    sim.number(at(10), -1.);
+    sim.connect(at(9), "list", at(2));
+    sim.connect(at(9), "index", at(10));
+    sim.place("list_positions", at(11));
+    sim.connect(at(11), "list", at(6));
+    sim.tick();
+
+    let list = |es: &[Entity]| Ok(Val::List(es.iter().copied().map(Val::Entity).collect()));
+    assert_eq!(sim.done(at(2)), list(&[a, c, b]));
+    assert_eq!(sim.done(at(3)), list(&[b, c, a]));
+    assert_eq!(sim.done(at(4)), list(&[c, a]));
+    assert_eq!(sim.done(at(6)), list(&[a]));
+    assert_eq!(sim.done(at(7)), list(&[c, a]));
+    assert_eq!(sim.done(at(9)), Ok(Val::Entity(b)));
+    match sim.done(at(11)) {
+        Ok(Val::List(l)) if l.len() == 1 => assert_vec(Ok(l[0].clone()), vec2(1., 0.)),
+        v => panic!("expected one position, got {:?}", v),
+    }
+}
+
+#[test]
+fn list_map_applies_a_node_to_every_element() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.place("constant_text", at(2));
+    sim.set_constant(at(2), Val::Text("entity_health".to_string()));
+    sim.place("list_sort_health", at(3));
+    sim.connect(at(3), "list", at(1));
+    sim.place("list_map", at(4));
+    sim.connect(at(4), "list", at(3));
+    sim.connect(at(4), "node", at(2));
+
+    sim.spawn_enemy(Hex::new(3, 0), 80.);
+    sim.spawn_enemy(Hex::new(4, 0), 30.);
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(4)),
+        Ok(Val::List(vec![Val::Number(30.), Val::Number(80.)]))
+    );
+
+    // nodes that store values or run other nodes can't be mapped
+    sim.set_constant(at(2), Val::Text("storage".to_string()));
+    sim.tick();
+    assert_eq!(
+        sim.done(at(4)),
+        Err(NodeError::UnknownName {
+            port: "node".to_string(),
+            name: "storage".to_string(),
+        })
+    );
+}
+
+#[test]
+fn list_index_out_of_range_errors() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.place("list", at(2));
+    sim.connect(at(2), "a", at(1));
+    sim.number(at(3), 1.);
+    sim.place("list_index", at(4));
+    sim.connect(at(4), "list", at(2));
+    sim.connect(at(4), "index", at(3));
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(4)),
+        Err(NodeError::OutOfRange {
+            port: "index".to_string(),
+            value: 1.,
+        })
+    );
+}
+
+#[test]
+fn project_occupies_target_tile_once() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(0., 3.), at(1), at(2));
+    sim.place("project", at(4));
+    sim.connect(at(4), "target", at(3));
+    sim.tick();
+
+    // the new tile makes every node idle again until the next heartbeat
+    assert_eq!(sim.state(at(4)), CyberState::Idle);
+    let world = sim.app.world();
+    let tile = world
+        .resource::<Map>()
+        .storage
+        .get(Hex::new(0, 3))
+        .copied()
+        .unwrap();
+    assert!(matches!(
+        world.get::<TileType>(tile),
+        Some(TileType::Terrain(..))
+    ));
+
+    sim.tick();
+    assert_eq!(sim.done(at(4)), Err(NodeError::Occupied(Hex::new(0, 3))));
+}
+
+#[test]
+fn launchers_and_debug_output_nothing() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(1., 1.), at(1), at(2));
+    sim.place("rocket_launcher", at(4));
+    sim.connect(at(4), "direction", at(3));
+    sim.place("orbital", at(5));
+    sim.connect(at(5), "target", at(3));
+    sim.place("debug", at(6));
+    sim.connect(at(6), "a", at(3));
+    sim.tick();
+
+    assert_eq!(sim.done(at(4)), Ok(Val::Empty));
+    assert_eq!(sim.done(at(5)), Ok(Val::Empty));
+    assert_eq!(sim.done(at(6)), Ok(Val::Empty));
+}
+
+#[test]
+fn storage_keeps_values_across_ticks() {
+    let mut sim = sim();
+    sim.number(at(1), 5.);
+    sim.place("storage", at(2));
+    sim.connect(at(2), "1", at(1));
+    sim.tick();
+    // slot 0 is read by default and was never written
+    assert_eq!(sim.done(at(2)), Ok(Val::Empty));
+
+    sim.number(at(3), 1.);
+    sim.connect(at(2), "slot", at(3));
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(5.)));
+
+    // inputs are stored at the end of the heartbeat
+    sim.set_constant(at(1), Val::Number(7.));
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(5.)));
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(7.)));
+
+    // without an input the stored value is kept
+    sim.disconnect(at(2), at(1));
+    sim.set_constant(at(1), Val::Number(9.));
+    sim.tick();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(7.)));
+}
+
+#[test]
+fn storage_slot_out_of_range_errors() {
+    let mut sim = sim();
+    sim.number(at(1), 4.);
+    sim.place("storage", at(2));
+    sim.connect(at(2), "slot", at(1));
+    sim.tick();
+
+    assert_eq!(
+        sim.done(at(2)),
+        Err(NodeError::OutOfRange {
+            port: "slot".to_string(),
+            value: 4.,
+        })
+    );
+}
+
+#[test]
+fn storage_feeds_back_into_a_loop() {
+    let mut sim = sim();
+    let storage = sim.place("storage", at(1));
+    sim.place("number_sub", at(2));
+    sim.number(at(3), 1.);
+    sim.connect(at(2), "a", at(1));
+    sim.connect(at(2), "b", at(3));
+    sim.connect(at(1), "0", at(2));
+    sim.app
+        .world_mut()
+        .entity_mut(storage)
+        .insert(StorageSlots([
+            Val::Number(10.),
+            Val::Empty,
+            Val::Empty,
+            Val::Empty,
+        ]));
+
+    sim.tick();
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(10.)));
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(9.)));
+    sim.tick();
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(9.)));
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(8.)));
+}
+
+#[test]
+fn chain_evaluates_in_one_heartbeat() {
+    let mut sim = sim();
+    // readers are spawned before the nodes they read from
+    sim.place("vector_len", at(1));
+    sim.place("vector_neg", at(2));
+    sim.connect(at(1), "vector", at(2));
+    sim.vector(at(3), vec2(3., 4.), at(4), at(5));
+    sim.connect(at(2), "vector", at(3));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(5.)));
+}
+
+#[test]
+fn loops_without_storage_error() {
+    let mut sim = sim();
+    sim.place("number_sub", at(1));
+    sim.place("number_mul", at(2));
+    sim.connect(at(1), "a", at(2));
+    sim.connect(at(2), "a", at(1));
+    sim.place("vector_neg", at(3));
+    sim.connect(at(3), "vector", at(2));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Err(NodeError::Cycle));
+    assert_eq!(sim.done(at(2)), Err(NodeError::Cycle));
+    // nodes fed by the loop still run and fail with it
+    assert_eq!(
+        sim.done(at(3)),
+        Err(NodeError::Upstream {
+            port: "vector".to_string(),
+            at: at(2),
+        })
+    );
+}
+
+#[test]
+fn loops_are_reported() {
+    let mut sim = sim();
+    sim.place("number_sub", at(1));
+    sim.place("number_mul", at(2));
+    sim.connect(at(1), "a", at(2));
+    sim.connect(at(2), "a", at(1));
+    sim.tick();
+
+    assert_eq!(sim.loops(), vec![at(1), at(2)]);
+
+    // the report only holds the last heartbeat
+    sim.disconnect(at(2), at(1));
+    sim.tick();
+
+    assert_eq!(sim.loops(), vec![]);
+}
+
+#[test]
+fn save_and_load_restores_the_board() {
+    let path = std::env::temp_dir().join(format!("cyberspace-save-{}.ron", std::process::id()));
+
+    let mut sim = sim();
+    sim.number(at(1), 5.);
+    sim.place("storage", at(2));
+    sim.connect(at(2), "1", at(1));
+    sim.place("closest_entity", at(3));
+    sim.place("storage", at(4));
+    sim.connect(at(4), "0", at(3));
+    sim.vector(at(-3), vec2(0., 3.), at(-1), at(-2));
+    sim.place("project", at(-4));
+    sim.connect(at(-4), "target", at(-3));
+    let e = sim.spawn_enemy(Hex::new(6, 0), 80.);
+    sim.app.world_mut().entity_mut(e).insert(PathfindPath {
+        path: vec![Hex::new(6, 0), Hex::new(5, 0)],
+        i: 1,
+    });
+    sim.tick();
+    sim.save(&path);
+    let before = sim.report();
+
+    let mut loaded = self::sim();
+    loaded.place("lazor", at(-1));
+    loaded.load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let after = loaded.report();
+
+    let layout =
+        |r: &SimReport| -> Vec<_> { r.nodes.iter().map(|n| (n.tile, n.node.clone())).collect() };
+    assert_eq!(layout(&after), layout(&before));
+    assert_eq!(after.heart_hp, before.heart_hp);
+    assert_eq!(after.credits, before.credits);
+    assert_eq!(after.enemies_alive, 1);
+
+    let world = loaded.app.world();
+    let tile = world
+        .resource::<Map>()
+        .storage
+        .get(Hex::new(0, 3))
+        .copied()
+        .unwrap();
+    assert!(matches!(
+        world.get::<TileType>(tile),
+        Some(TileType::Terrain(..))
+    ));
+
+    // the stored enemy points to the loaded one
+    let enemy = loaded
+        .app
+        .world_mut()
+        .query_filtered::<Entity, With<TargetableEntity>>()
+        .single(loaded.app.world());
+    assert_eq!(loaded.hp(enemy), 80.);
+    assert_eq!(loaded.slots(at(4))[0], Val::Entity(enemy));
+    assert_eq!(loaded.slots(at(2))[1], Val::Number(5.));
+
+    // wiring and constants still work
+    loaded.set_constant(at(1), Val::Number(6.));
+    loaded.tick();
+    assert_eq!(loaded.done(at(1)), Ok(Val::Number(6.)));
+    assert_eq!(loaded.slots(at(2))[1], Val::Number(6.));
+}
+
+#[test]
+fn board_is_saved_after_the_heart_is_destroyed() {
+    let path = std::env::temp_dir().join(format!("cyberspace-rip-{}.ron", std::process::id()));
+    let mut sim = sim();
+    sim.number(at(1), 5.);
+    let heart = sim.heart();
+    sim.app.world_mut().get_mut::<Health>(heart).unwrap().0 = 0.;
+    let report = sim.heartbeats(1);
+    assert_eq!(report.heart_hp, None);
+    sim.save(&path);
+
+    let mut loaded = self::sim();
+    loaded.load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let nodes: Vec<_> = loaded.report().nodes.iter().map(|n| n.tile).collect();
+    assert_eq!(nodes, vec![at(1)]);
+}
+
+#[test]
+fn loading_a_missing_save_keeps_the_board() {
+    let mut sim = sim();
+    sim.number(at(1), 5.);
+    sim.load(std::env::temp_dir().join("cyberspace-does-not-exist.ron"));
+    sim.tick();
+
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(5.)));
+}
+
+#[test]
+fn blueprint_pastes_rotated_circuit() {
+    let mut sim = sim();
+    sim.vector(at(3), vec2(1., 2.), at(1), at(2));
+    let bp = sim.copy(at(2), 1);
+    assert_eq!(bp.nodes.len(), 3);
+
+    // half a turn mirrors the line to the other side of the heart
+    let bp = Blueprint::from_text(&bp.to_text().unwrap()).unwrap();
+    assert_eq!(sim.paste(&bp.rotated(3), at(-2)), 3);
+    sim.tick();
+
+    assert_vec(sim.done(at(-3)), vec2(1., 2.));
+    assert_eq!(sim.done(at(-1)), Ok(Val::Number(1.)));
+    assert_eq!(sim.done(at(-2)), Ok(Val::Number(2.)));
+}
+
+#[test]
+fn blueprint_skips_occupied_tiles() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.number(at(2), 2.);
+    let bp = sim.copy(at(1), 1);
+
+    assert_eq!(sim.paste(&bp, at(1)), 0);
+    // the heart is in the way of the second node
+    assert_eq!(sim.paste(&bp, at(-1)), 1);
+    sim.tick();
+
+    assert_eq!(sim.done(at(-1)), Ok(Val::Number(1.)));
+}
+
+#[test]
+fn blueprint_skips_unconnected_tiles() {
+    let mut sim = sim();
+    sim.number(at(1), 1.);
+    sim.number(at(2), 2.);
+    let bp = sim.copy(at(1), 1);
+
+    // nothing next to the heart to grow from
+    assert_eq!(sim.paste(&bp, at(-4)), 0);
+    // the node next to the heart connects the one behind it
+    assert_eq!(sim.paste(&bp.rotated(3), at(-1)), 2);
+}
+
+#[test]
+fn wiring_checks_port_types() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.place("lazor", at(2));
+    sim.connect(at(2), "target", at(1));
+    assert_eq!(
+        sim.wiring(at(2)),
+        vec![WiringError::Mismatch {
+            port: "target".to_string(),
+            expected: ValType::Entity,
+            got: ValType::List,
+        }]
+    );
+
+    sim.place("closest_entity", at(3));
+    sim.connect(at(2), "target", at(3));
+    assert_eq!(sim.wiring(at(2)), vec![]);
+}
+
+#[test]
+fn wiring_any_matches_everything() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.place("storage", at(2));
+    sim.connect(at(2), "0", at(1));
+    sim.place("lazor", at(3));
+    sim.connect(at(3), "target", at(2));
+
+    assert_eq!(sim.wiring(at(2)), vec![]);
+    assert_eq!(sim.wiring(at(3)), vec![]);
+
+    sim.connect(at(3), "target", at(4));
+    assert_eq!(
+        sim.wiring(at(3)),
+        vec![WiringError::NoNode {
+            port: "target".to_string(),
+            at: at(4),
+        }]
+    );
+}
+
+#[test]
+fn breakpoint_pauses_halfway_through_a_heartbeat() {
+    let mut sim = sim();
+    sim.number(at(1), 12.);
+    sim.number(at(2), 0.);
+    sim.place("number_abs", at(3));
+    sim.place("number_div", at(4));
+    sim.connect(at(4), "a", at(1));
+    sim.connect(at(4), "b", at(2));
+    sim.connect(at(3), "number", at(4));
+    sim.app
+        .world_mut()
+        .resource_mut::<Debugger>()
+        .breakpoints
+        .insert(at(4), Breakpoint::Error);
+    sim.tick();
+
+    assert!(sim.done(at(4)).is_err());
+    assert_eq!(sim.state(at(3)), CyberState::Idle);
+    let debugger = sim.app.world().resource::<Debugger>();
+    assert!(debugger.paused);
+    assert_eq!(debugger.hit, Some(at(4)));
+
+    sim.app.world_mut().resource_mut::<Debugger>().paused = false;
+    sim.settle();
+    assert_eq!(
+        sim.done(at(3)),
+        Err(NodeError::Upstream {
+            port: "number".to_string(),
+            at: at(4),
+        })
+    );
+}
+
+#[test]
+fn typed_breakpoint_values() {
+    assert_eq!(
+        Breakpoint::typed("-2.5"),
+        Breakpoint::Value(Val::Number(-2.5))
+    );
+    assert_eq!(
+        Breakpoint::typed("true"),
+        Breakpoint::Value(Val::Bool(true))
+    );
+    assert_eq!(
+        Breakpoint::typed("1, 2"),
+        Breakpoint::Value(Val::Vec(vec2(1., 2.)))
+    );
+    assert_eq!(
+        Breakpoint::typed(" tank "),
+        Breakpoint::Value(Val::Text("tank".to_string()))
+    );
+}
+
+#[test]
+fn breakpoint_pauses_on_a_typed_value() {
+    let mut sim = sim();
+    sim.number(at(1), 2.);
+    sim.number(at(2), 3.);
+    sim.place("number_add", at(3));
+    sim.connect(at(3), "a", at(1));
+    sim.connect(at(3), "b", at(2));
+    sim.app
+        .world_mut()
+        .resource_mut::<Debugger>()
+        .breakpoints
+        .insert(at(3), Breakpoint::typed("5"));
+    sim.tick();
+
+    let debugger = sim.app.world().resource::<Debugger>();
+    assert!(debugger.paused);
+    assert_eq!(debugger.hit, Some(at(3)));
+}
+
+#[test]
+fn paused_debugger_steps_one_node() {
+    let mut sim = sim();
+    sim.number(at(1), 2.);
+    sim.number(at(2), 3.);
+    sim.app.world_mut().resource_mut::<Debugger>().paused = true;
+    sim.settle();
+    assert_eq!(sim.state(at(1)), CyberState::Idle);
+
+    sim.app.world_mut().resource_mut::<Debugger>().step = Some(Step::Node);
+    sim.settle();
+    assert_eq!(sim.done(at(1)), Ok(Val::Number(2.)));
+    assert_eq!(sim.state(at(2)), CyberState::Idle);
+
+    sim.app.world_mut().resource_mut::<Debugger>().step = Some(Step::Heartbeat);
+    sim.settle();
+    assert_eq!(sim.done(at(2)), Ok(Val::Number(3.)));
+}
+
+#[test]
+fn paused_debugger_stops_rockets() {
+    let mut sim = sim();
+    let layout = &sim.app.world().resource::<Map>().layout;
+    let dir = layout.hex_to_world_pos(at(9)) - layout.hex_to_world_pos(at(4));
+    sim.vector(at(3), dir.normalize(), at(1), at(2));
+    sim.place("rocket_launcher", at(4));
+    sim.connect(at(4), "direction", at(3));
+    let e = sim.spawn_enemy(at(9), 100.);
+    sim.tick();
+
+    // the rocket is in flight, but doesn't get anywhere
+    sim.app.world_mut().resource_mut::<Debugger>().paused = true;
+    for _ in 0..120 {
+        sim.step();
+    }
+    assert_eq!(sim.hp(e), 100.);
+
+    sim.app.world_mut().resource_mut::<Debugger>().paused = false;
+    let hit = (0..120).any(|_| {
+        sim.step();
+        sim.hp(e) < 100.
+    });
+    assert!(hit);
+}
+
+#[test]
+fn nodes_cost_credits_and_kills_pay() {
+    let mut sim = sim();
+    sim.app.world_mut().insert_resource(Credits(30));
+    assert!(sim.buy("lazor", at(1)).is_some());
+    assert_eq!(sim.credits(), 0);
+    assert_eq!(sim.buy("constant_number", at(2)), None);
+
+    sim.app.world_mut().send_event(EnemyKilled);
+    sim.step();
+    assert_eq!(sim.credits(), KILL_REWARD);
+
+    // half of the price is paid back
+    sim.app.world_mut().send_event(TileClicked {
+        tile: at(1),
+        mouse: Vec2::ZERO,
+        button: MouseButton::Right,
+    });
+    sim.step();
+    assert_eq!(sim.node_at(at(1)), None);
+    assert_eq!(sim.credits(), KILL_REWARD + 15);
+}
+
+#[test]
+fn waves_wait_space_out_and_grow() {
+    let schedule = WaveSchedule {
+        waves: vec![WaveDef {
+            delay: 2,
+            spacing: 1,
+            groups: vec![EnemyGroup {
+                archetype: Archetype::new("runner"),
+                count: 2,
+                hp: (50., 50.),
+            }],
+        }],
+        hp_growth: 2.,
+        count_growth: 1.5,
+        archetypes: Default::default(),
+    };
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut director = WaveDirector::default();
+
+    let mut hp = |director: &mut WaveDirector| {
+        director.beat(&schedule, &mut rng).map(|(archetype, hp)| {
+            assert_eq!(archetype, Archetype::new("runner"));
+            hp
+        })
+    };
+
+    let spawned: Vec<_> = (0..8).map(|_| hp(&mut director)).collect();
+    assert_eq!(
+        spawned,
+        vec![
+            None,
+            None,
+            Some(50.),
+            None,
+            Some(50.),
+            None,
+            None,
+            Some(100.)
+        ]
+    );
+    // the repeated wave has grown to three enemies
+    assert_eq!(director.wave, 2);
+    assert_eq!(hp(&mut director), None);
+    assert_eq!(hp(&mut director), Some(100.));
+    assert_eq!(hp(&mut director), None);
+    assert_eq!(hp(&mut director), Some(100.));
+    assert!(!director.spawning());
+}
+
+#[test]
+fn shields_ignore_some_damage() {
+    let mut sim = sim();
+    sim.place("closest_entity", at(1));
+    sim.place("lazor", at(2));
+    sim.connect(at(2), "target", at(1));
+    let e = sim.spawn_enemy(Hex::new(3, 0), 100.);
+    sim.app
+        .world_mut()
+        .entity_mut(e)
+        .insert(Shield(vec![DamageKind::Beam]));
+    sim.tick();
+
+    assert_eq!(sim.done(at(2)), Ok(Val::Empty));
+    assert_eq!(sim.hp(e), 100.);
+}
+
+#[test]
+fn archetypes_are_visible_to_circuits() {
+    let mut sim = sim();
+    sim.place("nearby_entities", at(1));
+    sim.place("constant_text", at(2));
+    sim.set_constant(at(2), Val::Text("tank".to_string()));
+    sim.place("list_filter_archetype", at(3));
+    sim.connect(at(3), "list", at(1));
+    sim.connect(at(3), "archetype", at(2));
+    sim.place("list_index", at(4));
+    sim.connect(at(4), "list", at(3));
+    sim.number(at(6), 0.);
+    sim.connect(at(4), "index", at(6));
+    sim.place("entity_archetype", at(5));
+    sim.connect(at(5), "target", at(4));
+
+    sim.spawn_enemy(Hex::new(1, 8), 100.);
+    let tank = sim.spawn_enemy(Hex::new(3, 0), 100.);
+    sim.app
+        .world_mut()
+        .entity_mut(tank)
+        .insert(Archetype::new("tank"));
+    sim.tick();
+
+    assert_eq!(sim.done(at(3)), Ok(Val::List(vec![Val::Entity(tank)])));
+    assert_eq!(sim.done(at(5)), Ok(Val::Text("tank".to_string())));
+
+    sim.set_constant(at(2), Val::Text("boss".to_string()));
+    sim.tick();
+    assert_eq!(
+        sim.done(at(3)),
+        Err(NodeError::UnknownName {
+            port: "archetype".to_string(),
+            name: "boss".to_string(),
+        })
+    );
+}
+
+#[test]
+fn splitters_split_into_runners() {
+    let mut sim = sim();
+    let path = PathfindPath {
+        path: vec![at(8), Hex::ZERO],
+        i: 1,
+    };
+    let e = sim.spawn_walker(at(8), path, "splitter");
+    sim.step();
+
+    sim.app.world_mut().get_mut::<Health>(e).unwrap().0 = 0.;
+    sim.step();
+
+    let world = sim.app.world_mut();
+    let runners: Vec<_> = world
+        .query_filtered::<(&Archetype, &Health), With<TargetableEntity>>()
+        .iter(world)
+        .map(|(a, hp)| (a.clone(), hp.0))
+        .collect();
+    // half the hp each, scaled by the runner's hp
+    assert_eq!(runners, vec![(Archetype::new("runner"), 20.); 3]);
+}
+
+#[test]
+fn archetypes_are_defined_by_the_schedule() {
+    let mut sim = sim();
+    let world = sim.app.world_mut();
+    let sniper = ArchetypeDef {
+        speed: 0.5,
+        range: Some(6),
+        shield: vec![DamageKind::Plasma],
+        ..default()
+    };
+    world
+        .resource_mut::<Archetypes>()
+        .0
+        .insert(Archetype::new("sniper"), sniper);
+
+    let path = PathfindPath {
+        path: vec![at(8), Hex::ZERO],
+        i: 1,
+    };
+    let e = sim.spawn_walker(at(8), path, "sniper");
+    sim.step();
+
+    let world = sim.app.world();
+    assert_eq!(world.get::<Speed>(e).unwrap().0, enemy::SPEED * 0.5);
+    assert_eq!(world.get::<Ranged>(e).unwrap().range, 6);
+    assert_eq!(world.get::<Shield>(e).unwrap().0, vec![DamageKind::Plasma]);
+}
+
+#[test]
+fn dead_enemies_play_the_death_clip_before_despawning() {
+    let mut sim = sim();
+    let path = PathfindPath {
+        path: vec![at(8), Hex::ZERO],
+        i: 1,
+    };
+    let e = sim.spawn_walker(at(8), path, "grunt");
+    sim.step();
+    assert_eq!(sim.app.world().get::<Animation>(e).unwrap().clip, Clip::Run);
+
+    sim.app.world_mut().get_mut::<Health>(e).unwrap().0 = 0.;
+    sim.step();
+    let world = sim.app.world();
+    assert!(world.get::<Dying>(e).is_some());
+    assert!(world.get::<TargetableEntity>(e).is_none());
+    assert_eq!(world.get::<Animation>(e).unwrap().clip, Clip::Death);
+
+    // nine frames of a tenth of a second
+    for _ in 0..100 {
+        sim.step();
+    }
+    assert!(sim.app.world().get_entity(e).is_none());
+}
+
+#[test]
+fn enemies_replan_around_new_nodes() {
+    let mut sim = sim();
+    let path = PathfindPath {
+        path: vec![at(8), Hex::ZERO],
+        i: 1,
+    };
+    let e = sim.spawn_walker(at(8), path, "grunt");
+    let path = |sim: &Sim| sim.app.world().get::<PathfindPath>(e).unwrap().path.clone();
+    sim.step();
+    assert_eq!(path(&sim), (0..=8).rev().map(at).collect::<Vec<_>>());
+
+    // changes are batched, the path is only replanned a little later
+    sim.place("constant_number", at(4));
+    for _ in 0..5 {
+        sim.step();
+    }
+    assert!(path(&sim).contains(&at(4)));
+    for _ in 0..20 {
+        sim.step();
+    }
+    let detour = path(&sim);
+    assert!(!detour.contains(&at(4)));
+    assert_eq!(detour.first(), Some(&at(8)));
+    assert_eq!(detour.last(), Some(&Hex::ZERO));
+
+    // free nodes are walked through again
+    sim.app.world_mut().resource_mut::<PathCosts>().cyber_node = 0;
+    for _ in 0..20 {
+        sim.step();
+    }
+    assert!(path(&sim).contains(&at(4)));
+}
+
+#[test]
+fn flow_fields_update_like_a_rebuild() {
+    let walls = |walls: &[Hex]| {
+        let walls = walls.to_vec();
+        move |h: Hex| if walls.contains(&h) { 50 } else { 1 }
+    };
+    let before = [
+        Hex::new(2, 0),
+        Hex::new(2, -1),
+        Hex::new(1, 1),
+        Hex::new(-3, 2),
+    ];
+    let after = [
+        Hex::new(2, 0),
+        Hex::new(3, -1),
+        Hex::new(-1, 1),
+        Hex::new(0, 3),
+    ];
+
+    let mut field = FlowField::new(Hex::ZERO, 6, walls(&before));
+    let mut changed = before.to_vec();
+    changed.extend(after);
+    field.update(&changed, walls(&after));
+
+    let rebuilt = FlowField::new(Hex::ZERO, 6, walls(&after));
+    for h in Hex::ZERO.range(7) {
+        assert_eq!(field.dist(h), rebuilt.dist(h), "at {:?}", h);
+    }
+}
+
+#[test]
+fn enemies_steer_around_nodes() {
+    let mut sim = sim();
+    let heart = sim.heart();
+    sim.place("constant_number", at(4));
+    let path = PathfindPath {
+        path: vec![at(8)],
+        i: 0,
+    };
+    let e = sim.spawn_walker(at(8), path, "runner");
+
+    let target = (0..3000).find_map(|_| {
+        sim.step();
+        let world = sim.app.world();
+        let pos = world.get::<Transform>(e).unwrap().translation.xy();
+        assert_ne!(world.resource::<Map>().layout.world_pos_to_hex(pos), at(4));
+        match world.get::<EnemyActivity>(e).unwrap() {
+            EnemyActivity::Attacking(t, _) => Some(*t),
+            EnemyActivity::Pathfinding => None,
+        }
+    });
+    assert_eq!(target, Some(heart));
+}
+
+#[test]
+fn bench_enemies_walk_to_the_heart() {
+    let mut sim = sim();
+    sim.spawn_enemies(20, 1000.);
+    // sorted, archetype traits move enemies between tables
+    let dists = |sim: &mut Sim| -> Vec<(Entity, f32)> {
+        let world = sim.app.world_mut();
+        let mut dists: Vec<_> = world
+            .query_filtered::<(Entity, &Transform), With<TargetableEntity>>()
+            .iter(world)
+            .map(|(e, t)| (e, t.translation.xy().length()))
+            .collect();
+        dists.sort_by_key(|(e, _)| *e);
+        dists
+    };
+    let before = dists(&mut sim);
+    assert_eq!(before.len(), 20);
+
+    assert_eq!(sim.time_steps(30).len(), 30);
+    let after = dists(&mut sim);
+    assert_eq!(after.len(), 20);
+    assert!(before.iter().zip(after.iter()).all(|(b, a)| a.1 < b.1));
+}
+
+#[test]
+fn duplicate_catalog_entries_are_skipped() {
+    let mut sim = sim();
+    let items = sim.app.world().resource::<NodeItems>().order.len();
+    let mut catalogs = sim.app.world_mut().resource_mut::<Assets<NodeCatalog>>();
+    let id = catalogs.ids().next().unwrap();
+    let catalog = catalogs.get_mut(id).unwrap();
+    catalog.nodes.push(catalog.nodes[0].clone());
+    sim.step();
+
+    assert_eq!(sim.app.world().resource::<NodeItems>().order.len(), items);
+}
+
+#[test]
+fn catalog_labels_follow_reloads() {
+    let mut sim = sim();
+    let label = |sim: &Sim, node: &str| {
+        let world = sim.app.world();
+        let item = world.resource::<NodeItems>().by_node[&NodeKind(node.to_string())];
+        world.get::<IconLabel>(item).map(|l| l.0.clone())
+    };
+    assert_eq!(label(&sim, "number_div").as_deref(), Some("/"));
+    assert_eq!(label(&sim, "lazor"), None);
+
+    let mut catalogs = sim.app.world_mut().resource_mut::<Assets<NodeCatalog>>();
+    let id = catalogs.ids().next().unwrap();
+    let catalog = catalogs.get_mut(id).unwrap();
+    for def in catalog.nodes.iter_mut() {
+        def.label = (def.node.0 == "lazor").then(|| "L".to_string());
+    }
+    sim.step();
+
+    assert_eq!(label(&sim, "number_div"), None);
+    assert_eq!(label(&sim, "lazor").as_deref(), Some("L"));
+}
+
+#[test]
+fn ranged_enemies_stop_at_terrain() {
+    let mut sim = sim();
+    sim.place("constant_number", at(3));
+    let world = sim.app.world_mut();
+    let rock = world.spawn((Health(100.), HexPos(at(6)))).id();
+    let te = *world.resource::<Map>().storage.get(at(6)).unwrap();
+    *world.get_mut::<TileType>(te).unwrap() = TileType::Terrain(rock);
+
+    let path = PathfindPath {
+        path: vec![at(6), Hex::ZERO],
+        i: 1,
+    };
+    let e = sim.spawn_walker(at(6), path, "ranged");
+    sim.step();
+    sim.step();
+
+    // the node is in range, but the rock is in the way
+    assert!(matches!(
+        sim.app.world().get::<EnemyActivity>(e),
+        Some(EnemyActivity::Attacking(t, _)) if *t == rock
+    ));
+}
+
+#[test]
+fn ranged_enemies_shoot_bolts() {
+    let mut sim = sim();
+    sim.place("constant_number", at(3));
+    let node = sim.node_at(at(3)).unwrap();
+    let path = PathfindPath {
+        path: vec![at(6), Hex::ZERO],
+        i: 1,
+    };
+    sim.spawn_walker(at(6), path, "ranged");
+
+    let bolts = |sim: &mut Sim| {
+        let world = sim.app.world_mut();
+        world.query::<&Bolt>().iter(world).count()
+    };
+    assert!((0..100).any(|_| {
+        sim.step();
+        bolts(&mut sim) > 0
+    }));
+    assert!(sim.hp(node) < NODE_HP);
+
+    // they vanish once they arrived
+    for _ in 0..20 {
+        sim.step();
+    }
+    assert_eq!(bolts(&mut sim), 0);
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::{
+    ecs::system::{lifetimeless::SCommands, SystemParamItem},
+    prelude::*,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy::{Archetype, ArchetypeDef, Archetypes},
+    ron_asset::{RegisterRonAsset, RonAsset},
+};
+
+pub const WAVES_PATH: &str = "enemy.waves.ron";
+
+pub struct WavePlugin;
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveDirector>()
+            .register_ron_asset::<WaveSchedule>();
+    }
+}
+
+// enemies of a wave that share an archetype and hp range
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyGroup {
+    #[serde(default)]
+    pub archetype: Archetype,
+    pub count: u32,
+    // the hp of each enemy is picked from this range, before the archetype scales it
+    pub hp: (f32, f32),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveDef {
+    // heartbeats to wait once the previous wave is spawned
+    pub delay: u32,
+    // heartbeats between two enemies of the wave
+    #[serde(default)]
+    pub spacing: u32,
+    // spawned in order
+    pub groups: Vec<EnemyGroup>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct WaveSchedule {
+    pub waves: Vec<WaveDef>,
+    // once the schedule ran out the last wave repeats, growing by these every time
+    pub hp_growth: f32,
+    pub count_growth: f32,
+    // what the archetypes of the groups do, by name
+    #[serde(default)]
+    pub archetypes: HashMap<Archetype, ArchetypeDef>,
+}
+
+impl WaveSchedule {
+    // `None` if there are no waves at all
+    pub fn wave(&self, i: u32) -> Option<WaveDef> {
+        if let Some(wave) = self.waves.get(i as usize) {
+            return Some(wave.clone());
+        }
+
+        let mut wave = self.waves.last()?.clone();
+        let repeats = (i as usize + 1 - self.waves.len()) as i32;
+        let hp = self.hp_growth.powi(repeats);
+        let count = self.count_growth.powi(repeats);
+        for g in wave.groups.iter_mut() {
+            g.hp = (g.hp.0 * hp, g.hp.1 * hp);
+            g.count = (g.count as f32 * count).round() as u32;
+        }
+        Some(wave)
+    }
+}
+
+// the schedule enemies are spawned by, once it is loaded
+#[derive(Resource)]
+pub struct Waves(pub WaveSchedule);
+
+// where the game is in the wave schedule, advanced every heartbeat
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WaveDirector {
+    // waves started so far
+    pub wave: u32,
+    // heartbeats until the next wave, counted down once the current one is spawned
+    pub countdown: Option<u32>,
+    // the enemies of the current wave that still have to spawn
+    pending: VecDeque<(Archetype, f32)>,
+    spacing: u32,
+    // heartbeats until the next enemy of the current wave
+    cooldown: u32,
+}
+
+impl WaveDirector {
+    pub fn spawning(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // advances by one heartbeat, returns the enemy to spawn if there is one
+    pub fn beat(
+        &mut self,
+        schedule: &WaveSchedule,
+        rng: &mut impl Rng,
+    ) -> Option<(Archetype, f32)> {
+        if self.pending.is_empty() {
+            let wave = schedule.wave(self.wave)?;
+            let countdown = self.countdown.get_or_insert(wave.delay);
+            if *countdown > 0 {
+                *countdown -= 1;
+                return None;
+            }
+
+            self.wave += 1;
+            self.countdown = schedule.wave(self.wave).map(|w| w.delay);
+            self.spacing = wave.spacing;
+            self.cooldown = 0;
+            for g in wave.groups.iter() {
+                let (min, max) = (g.hp.0.min(g.hp.1), g.hp.0.max(g.hp.1));
+                for _ in 0..g.count {
+                    self.pending
+                        .push_back((g.archetype.clone(), rng.gen_range(min..=max)));
+                }
+            }
+            info!("wave {} with {} enemies", self.wave, self.pending.len());
+        }
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return None;
+        }
+        self.cooldown = self.spacing;
+        self.pending.pop_front()
+    }
+}
+
+impl RonAsset for WaveSchedule {
+    const PATH: &'static str = WAVES_PATH;
+    const EXTENSIONS: &'static [&'static str] = &["waves.ron"];
+
+    type Param = SCommands;
+
+    fn apply(&self, cmd: &mut SystemParamItem<Self::Param>) {
+        info!("applying wave schedule with {} waves", self.waves.len());
+        let groups = self.waves.iter().flat_map(|w| w.groups.iter());
+        for g in groups.filter(|g| !self.archetypes.contains_key(&g.archetype)) {
+            warn!(
+                "archetype {:?} is not in the wave schedule, it spawns plain",
+                g.archetype.0
+            );
+        }
+        cmd.insert_resource(Archetypes(self.archetypes.clone()));
+        cmd.insert_resource(Waves(self.clone()));
+    }
+}